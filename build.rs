@@ -1,3 +1,9 @@
+use std::env;
+
 fn main() {
-    println!("cargo:rustc-link-search=/opt/homebrew/lib/");
+    // Homebrew puts SDL2 here on Apple Silicon, outside the default search path
+    let sdl = env::var_os("CARGO_FEATURE_SDL").is_some();
+    if sdl && env::var("CARGO_CFG_TARGET_OS").as_deref() == Ok("macos") {
+        println!("cargo:rustc-link-search=/opt/homebrew/lib/");
+    }
 }
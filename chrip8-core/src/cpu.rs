@@ -0,0 +1,1435 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::{io, thread};
+#[cfg(feature = "std")]
+use std::fs::File;
+#[cfg(feature = "std")]
+use std::io::{BufReader, Read};
+#[cfg(feature = "std")]
+use std::sync::Arc;
+#[cfg(feature = "std")]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "std")]
+use std::sync::mpsc::{self, Receiver, Sender};
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+use core::mem;
+use log::{debug, info, warn};
+
+#[cfg(feature = "std")]
+pub use crate::cpu::control::Control;
+#[cfg(feature = "std")]
+use crate::cpu::control::Command;
+pub use crate::cpu::control::StopReason;
+pub use crate::cpu::builder::{Chip8Builder, Platform, DEFAULT_FONT_ADDRESS, DEFAULT_PROGRAM_START, FONT, MAX_MEMORY_SIZE};
+pub use crate::cpu::debugger::{Debugger, Snapshot};
+pub use crate::cpu::database::{sha1, RomDatabase, RomInfo};
+pub use crate::cpu::instruction::Instruction;
+pub use crate::cpu::observer::{Observer, ObserverId, Timer};
+use crate::cpu::observer::Observers;
+use crate::cpu::movie::{Movie, MoviePlayer};
+#[cfg(feature = "std")]
+use crate::cpu::movie::MovieRecorder;
+pub use crate::cpu::protection::{Protection, ProtectionAction};
+pub use crate::cpu::quirks::Quirks;
+pub use crate::cpu::random::{Random, RandomAlgorithm};
+pub use crate::cpu::sanitizer::{Finding, FindingKind};
+use crate::cpu::sanitizer::Sanitizer;
+use crate::cpu::rewind::RewindBuffer;
+
+mod builder;
+mod control;
+mod database;
+mod debugger;
+mod instruction;
+mod movie;
+mod observer;
+mod protection;
+mod quirks;
+mod random;
+mod rewind;
+mod sanitizer;
+
+pub const FRAMES_PER_SECOND: u32 = 60;
+#[cfg(feature = "std")]
+const FRAME_DURATION: Duration = Duration::from_micros(1_000_000 / FRAMES_PER_SECOND as u64);
+
+pub trait Display : Send {
+    /// Shows a finished frame. Called at most once per 60 Hz frame, and only when the screen
+    /// has changed.
+    fn draw(&self, display: [[bool; 32]; 64]);
+
+    /// Called when the sound timer starts or stops the buzzer.
+    fn sound(&self, _on: bool) {}
+}
+
+pub trait Input : Send {
+    fn is_key_pressed(&self, key: u8) -> bool;
+
+    /// While this returns true the machine runs backwards through its rewind buffer.
+    fn is_rewind_pressed(&self) -> bool {
+        return false;
+    }
+
+    /// Returns true once for every single frame requested while paused.
+    fn take_step(&self) -> bool {
+        return false;
+    }
+}
+
+pub struct Chip8 {
+    ram: Vec<u8>,
+    display: [[bool; 32]; 64],
+    pc: u16,
+    i: u16,
+    stack: Vec<u16>,
+    delay_timer: u8,
+    sound_timer: u8,
+    registers: [u8; 16],
+    keypad: u16,
+    previous_keypad: u16,
+    random: Random,
+    rom_hash: u64,
+    rom_info: Option<RomInfo>,
+    last_instruction: Option<Instruction>,
+    /// Where `last_instruction` was fetched from.
+    instruction_address: u16,
+    vblank_wait: bool,
+    /// Set by 00FD; the machine runs no more instructions.
+    exited: bool,
+    /// Set by a write to protected memory with `ProtectionAction::Trap`.
+    trap: Option<String>,
+    display_dirty: bool,
+    sound_on: bool,
+    options: Chip8Options,
+    rewind: Option<RewindBuffer>,
+    movie: Option<MovieSession>,
+    observers: Observers,
+    sanitizer: Option<Sanitizer>,
+    /// The last ROM loaded, for `reset`.
+    rom: Vec<u8>,
+    #[cfg(feature = "std")]
+    commands: Option<(Sender<Command>, Receiver<Command>)>,
+    /// Shared with every `Control`, so that they all see a trap pause the machine.
+    #[cfg(feature = "std")]
+    paused: Arc<AtomicBool>,
+    input: Box<dyn Input>,
+    display_output: Box<dyn Display>,
+}
+
+struct Chip8Options {
+    quirks: Quirks,
+    instructions_per_frame: u32,
+    database: Option<RomDatabase>,
+    font: [u8; 80],
+    font_address: u16,
+    program_start: u16,
+    /// V0 to VF at power on.
+    registers: [u8; 16],
+    protection: Option<Protection>,
+}
+
+enum MovieSession {
+    #[cfg(feature = "std")]
+    Recording(MovieRecorder),
+    Playing(MoviePlayer),
+}
+
+/// A full copy of the machine state at the end of a frame.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chip8State {
+    pub ram: Vec<u8>,
+    pub display: [[bool; 32]; 64],
+    pub pc: u16,
+    pub i: u16,
+    pub stack: Vec<u16>,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub registers: [u8; 16],
+    pub random: Random,
+}
+
+impl Chip8 {
+    /// A machine with the default configuration; see `builder` for anything else.
+    pub fn new(input: Box<dyn Input>, display: Box<dyn Display>) -> Chip8 {
+        return Chip8Builder::new(input, display).build().expect("the default configuration is valid");
+    }
+
+    pub fn builder(input: Box<dyn Input>, display: Box<dyn Display>) -> Chip8Builder {
+        return Chip8Builder::new(input, display);
+    }
+
+    #[allow(clippy::needless_range_loop)]
+    fn clear_screen(&mut self) {
+        for x in 0..(self.display.len()) {
+            for y in 0..(self.display[x].len()) {
+                self.display[x][y] = false;
+            }
+        }
+    }
+
+    /// Swaps in another input, e.g. to hand a running machine from the keyboard to a script,
+    /// and gives back the one it replaces.
+    pub fn replace_input(&mut self, input: Box<dyn Input>) -> Box<dyn Input> {
+        return mem::replace(&mut self.input, input);
+    }
+
+    /// Swaps in another display, e.g. to move a running machine from a window to headless
+    /// mode and back. The new display is shown the current screen at the end of the next frame.
+    pub fn replace_display(&mut self, display: Box<dyn Display>) -> Box<dyn Display> {
+        let previous = mem::replace(&mut self.display_output, display);
+        self.display_dirty = true;
+        if self.sound_on {
+            previous.sound(false);
+            self.display_output.sound(true);
+        }
+        return previous;
+    }
+
+    /// Keeps the last `seconds` of frames so that they can be played backwards.
+    pub fn enable_rewind(&mut self, seconds: u32) -> Result<(), String> {
+        let frames = seconds.checked_mul(FRAMES_PER_SECOND).ok_or(format!("Cannot rewind {} seconds", seconds))?;
+        self.rewind = Some(RewindBuffer::new(frames as usize));
+        Ok(())
+    }
+
+    pub fn save_state(&self) -> Chip8State {
+        return Chip8State {
+            ram: self.ram.clone(),
+            display: self.display,
+            pc: self.pc,
+            i: self.i,
+            stack: self.stack.clone(),
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+            registers: self.registers,
+            random: self.random.clone(),
+        };
+    }
+
+    pub fn load_state(&mut self, state: &Chip8State) {
+        self.ram = state.ram.clone();
+        self.display = state.display;
+        self.pc = state.pc;
+        self.i = state.i;
+        self.stack = state.stack.clone();
+        self.delay_timer = state.delay_timer;
+        self.sound_timer = state.sound_timer;
+        self.registers = state.registers;
+        self.random = state.random.clone();
+        self.exited = false;
+        self.display_dirty = true;
+    }
+
+    /// Starts the last ROM loaded over, with memory, screen, registers and timers as they were
+    /// when the machine was built. Quirks, speed and the RNG stay as they are.
+    pub fn reset(&mut self) {
+        let rom = mem::take(&mut self.rom);
+        self.power_on();
+        self.load_rom_bytes(rom);
+    }
+
+    fn power_on(&mut self) {
+        if self.movie.take().is_some() {
+            warn!("The movie stops, it cannot follow a reset");
+        }
+        self.ram.fill(0);
+        let font_address = self.options.font_address as usize;
+        self.ram[font_address..font_address + self.options.font.len()].copy_from_slice(&self.options.font);
+        self.clear_screen();
+        self.i = 0;
+        self.stack.clear();
+        self.delay_timer = 0;
+        self.sound_timer = 0;
+        self.registers = self.options.registers;
+        self.last_instruction = None;
+        self.vblank_wait = false;
+        self.exited = false;
+        self.trap = None;
+        self.display_dirty = true;
+    }
+
+    /// A handle to pause, reset or stop the machine while `execute` runs on another thread.
+    #[cfg(feature = "std")]
+    pub fn control(&mut self) -> Control {
+        let (commands, _) = self.commands.get_or_insert_with(mpsc::channel);
+        return Control { commands: commands.clone(), paused: self.paused.clone() };
+    }
+
+    #[cfg(feature = "std")]
+    pub fn load_rom_file(&mut self, rom: String) -> io::Result<()> {
+        let file = File::open(rom)?;
+        let mut reader = BufReader::new(file);
+        let mut buffer = Vec::new();
+
+        // Read file into vector.
+        reader.read_to_end(&mut buffer)?;
+
+        // load to ram
+        self.load_rom_bytes(buffer);
+
+        Ok(())
+    }
+
+    /// Writes straight into RAM, for `Debugger::poke`.
+    fn poke(&mut self, address: u16, value: u8) {
+        let address = address as usize % self.ram.len();
+        if let Some(sanitizer) = self.sanitizer.as_mut() {
+            sanitizer.poked(address);
+        }
+        // cannot fail, the address is wrapped into memory
+        let _ = self.store_ram(address, value);
+    }
+
+    /// Every write an instruction makes goes through here, so that protected memory is
+    /// checked before it is written.
+    fn write_ram(&mut self, address: usize, value: u8) -> Result<(), String> {
+        self.check_write(address, value)?;
+        if let Some(sanitizer) = self.sanitizer.as_mut() {
+            sanitizer.written(self.instruction_address, address);
+        }
+        return self.store_ram(address, value);
+    }
+
+    /// Reads a byte an instruction asked for, failing instead of panicking past the end of memory.
+    fn read_ram(&self, address: usize) -> Result<u8, String> {
+        return match self.ram.get(address) {
+            Some(value) => Ok(*value),
+            None => Err(format!("Read outside memory at {:#06x}", address)),
+        };
+    }
+
+    /// Writes a byte into RAM where observers see it, without checking protection.
+    fn store_ram(&mut self, address: usize, value: u8) -> Result<(), String> {
+        let old = match self.ram.get(address) {
+            Some(old) => *old,
+            None => return Err(format!("Write outside memory at {:#06x}", address)),
+        };
+        self.ram[address] = value;
+        self.observers.each(|observer| observer.memory_written(address as u16, old, value));
+        Ok(())
+    }
+
+    /// Starts telling an observer what the machine does, until it is removed again.
+    pub fn add_observer(&mut self, observer: Box<dyn Observer>) -> ObserverId {
+        return self.observers.add(observer);
+    }
+
+    pub fn remove_observer(&mut self, id: ObserverId) -> Option<Box<dyn Observer>> {
+        return self.observers.remove(id);
+    }
+
+    /// Reseeds the CXNN random number generator, so that the run can be reproduced.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.random = Random::new(self.random.algorithm, seed);
+    }
+
+    pub fn set_random_algorithm(&mut self, algorithm: RandomAlgorithm) {
+        self.random = Random::new(algorithm, self.random.seed);
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.options.quirks = quirks;
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        return self.options.quirks;
+    }
+
+    pub fn set_instructions_per_frame(&mut self, instructions_per_frame: u32) -> Result<(), String> {
+        if instructions_per_frame == 0 {
+            return Err(String::from("At least one instruction has to run per frame"));
+        }
+        self.options.instructions_per_frame = instructions_per_frame;
+        Ok(())
+    }
+
+    pub fn instructions_per_frame(&self) -> u32 {
+        return self.options.instructions_per_frame;
+    }
+
+    /// ROMs loaded from now on are looked up here, and known ones get their quirks and speed.
+    pub fn set_database(&mut self, database: RomDatabase) {
+        self.options.database = Some(database);
+    }
+
+    /// The database entry of the loaded ROM, if it has one.
+    pub fn rom_info(&self) -> Option<&RomInfo> {
+        return self.rom_info.as_ref();
+    }
+
+    /// Starts writing every frame's keypad state to a movie file.
+    /// Call it right after loading the ROM, so that playback starts from the same state.
+    #[cfg(feature = "std")]
+    pub fn record_movie(&mut self, path: String) -> io::Result<()> {
+        let recorder = MovieRecorder::create(path, &self.movie_header())?;
+        self.movie = Some(MovieSession::Recording(recorder));
+        Ok(())
+    }
+
+    /// Replays the keypad states of a recorded movie instead of the live input.
+    /// Call it right after loading the ROM the movie was recorded with.
+    #[cfg(feature = "std")]
+    pub fn play_movie(&mut self, path: String) -> Result<(), String> {
+        let movie = Movie::load(path).map_err(|e| e.to_string())?;
+        return self.start_movie(movie);
+    }
+
+    /// Like `play_movie`, with the movie file already read into memory.
+    pub fn play_movie_text(&mut self, text: &str) -> Result<(), String> {
+        return self.start_movie(Movie::parse(text)?);
+    }
+
+    /// What a movie of this machine records before its frames.
+    fn movie_header(&self) -> Movie {
+        return Movie {
+            rom_hash: self.rom_hash,
+            quirks: self.options.quirks,
+            random_algorithm: self.random.algorithm,
+            seed: self.random.seed,
+            instructions_per_frame: self.options.instructions_per_frame,
+            memory_size: self.ram.len(),
+            font_address: self.options.font_address,
+            program_start: self.options.program_start,
+            registers: self.options.registers,
+            frames: Vec::new(),
+        };
+    }
+
+    fn start_movie(&mut self, movie: Movie) -> Result<(), String> {
+        let machine = self.movie_header();
+        if movie.rom_hash != machine.rom_hash {
+            return Err(format!("Movie was recorded with ROM {:016x}, but ROM {:016x} is loaded", movie.rom_hash, machine.rom_hash));
+        }
+        if movie.instructions_per_frame != machine.instructions_per_frame {
+            return Err(format!("Movie was recorded at {} instructions per frame, but the machine runs {}", movie.instructions_per_frame, machine.instructions_per_frame));
+        }
+        let layout = |movie: &Movie| (movie.memory_size, movie.font_address, movie.program_start, movie.registers);
+        if layout(&movie) != layout(&machine) {
+            return Err(format!(
+                "Movie was recorded with {} bytes of memory, the font at {:#05x}, programs at {:#05x} and registers {:?}, which this machine does not have",
+                movie.memory_size, movie.font_address, movie.program_start, movie.registers,
+            ));
+        }
+
+        self.options.quirks = movie.quirks;
+        self.random = Random::new(movie.random_algorithm, movie.seed);
+        self.movie = Some(MovieSession::Playing(MoviePlayer::new(movie)));
+        Ok(())
+    }
+
+    pub fn load_rom_bytes(&mut self, buffer: Vec<u8>) {
+        self.rom = buffer.clone();
+        self.rom_hash = movie::hash(&buffer);
+        self.rom_info = self.options.database.as_ref().and_then(|database| database.lookup(&buffer)).cloned();
+        if let Some(info) = &self.rom_info {
+            info!("Recognised ROM: {}", info.title);
+            if let Some(quirks) = info.quirks {
+                self.options.quirks = quirks;
+            }
+            if let Some(instructions_per_frame) = info.instructions_per_frame.filter(|&instructions| instructions > 0) {
+                self.options.instructions_per_frame = instructions_per_frame;
+            }
+        }
+        let start = self.options.program_start as usize;
+        if buffer.len() > self.ram.len() - start {
+            warn!("ROM of {} bytes does not fit into memory, only {} bytes are loaded", buffer.len(), self.ram.len() - start);
+        }
+        for (i, value) in buffer.into_iter().take(self.ram.len() - start).enumerate() {
+            self.ram[start + i] = value;
+        }
+
+        self.pc = self.options.program_start;
+        self.sanitizer_loaded();
+        if let Some(rewind) = self.rewind.as_mut() {
+            rewind.clear();
+        }
+    }
+
+    fn fetch_instruction(&mut self) -> Result<Instruction, String> {
+        if self.pc as usize + 1 >= self.ram.len() {
+            return Err(String::from("Out of Memory!"));
+        }
+
+        // the PC wraps around after the last instruction of a 64 KB machine
+        let first_byte = self.ram[self.pc as usize];
+        self.pc = self.pc.wrapping_add(1);
+        let second_byte = self.ram[self.pc as usize];
+        self.pc = self.pc.wrapping_add(1);
+
+        let instruction = Instruction::new(first_byte, second_byte);
+        return Ok(instruction);
+    }
+
+    fn subroutine_return(&mut self) -> Result<(), String> {
+        if let Some(sanitizer) = self.sanitizer.as_mut().filter(|_| self.stack.is_empty()) {
+            sanitizer.returned_without_call(self.instruction_address);
+        }
+        self.pc = self.stack.pop().ok_or("Return from a subroutine with an empty stack")?;
+        let (address, depth) = (self.pc, self.stack.len());
+        self.observers.each(|observer| observer.stack_popped(address, depth));
+        Ok(())
+    }
+
+    fn call_subroutine(&mut self, address: u16) {
+        self.stack.push(self.pc);
+        let (return_address, depth) = (self.pc, self.stack.len());
+        if let Some(sanitizer) = self.sanitizer.as_mut() {
+            sanitizer.called(self.instruction_address, depth);
+        }
+        self.observers.each(|observer| observer.stack_pushed(return_address, depth));
+        self.pc = address;
+    }
+
+    fn skip_if_equals(&mut self, a: u8, b: u8) {
+        if a == b {
+            self.pc = self.pc.wrapping_add(2);
+        }
+    }
+
+    fn skip_if_not_equals(&mut self, a: u8, b: u8) {
+        if a != b {
+            self.pc = self.pc.wrapping_add(2);
+        }
+    }
+
+    fn jump(&mut self, location: u16) {
+        debug!("JUMP {}", location);
+        self.pc = location;
+    }
+
+    fn jump_with_offset(&mut self, location: u16) {
+        // with the jumping quirk BXNN adds VX, where X is the high nibble of the address
+        let register = if self.options.quirks.jumping { (location >> 8) as u8 } else { 0x0 };
+        debug!("JUMP_OFFSET REG[{}]={} + {}", register, self.register_get_value(register), location);
+        self.jump((self.register_get_value(register) as u16) + location);
+    }
+
+    fn register_set_value(&mut self, register: u8, value: u8) {
+        debug!("REG_SET_VALUE REG[{}]={}", register, value);
+        self.registers[usize::from(register)] = value;
+    }
+
+    fn set_index_register(&mut self, value: u16) {
+        debug!("INDEX_SET_VALUE {}", value);
+        self.i = value;
+    }
+
+    fn register_add_value(&mut self, register: u8, value: u8) {
+        let a: u8 = self.registers[register as usize];
+        let b: u8 = value;
+        let (sum, _overflows) = a.overflowing_add(b);
+        self.registers[usize::from(register)] = sum;
+    }
+
+    fn register_set(&mut self, register_a: u8, register_b: u8) {
+        self.registers[register_a as usize] = self.registers[register_b as usize];
+    }
+
+    fn register_get_value(&self, register: u8) -> u8 {
+        return self.registers[(register as usize) & 0xF];
+    }
+
+    #[allow(clippy::assign_op_pattern)]
+    fn register_or(&mut self, register_a: u8, register_b: u8) {
+        self.registers[register_a as usize] = self.registers[register_a as usize] | self.registers[register_b as usize];
+        self.vf_reset();
+    }
+
+    #[allow(clippy::assign_op_pattern)]
+    fn register_and(&mut self, register_a: u8, register_b: u8) {
+        self.registers[register_a as usize] = self.registers[register_a as usize] & self.registers[register_b as usize];
+        self.vf_reset();
+    }
+
+    #[allow(clippy::assign_op_pattern)]
+    fn register_xor(&mut self, register_a: u8, register_b: u8) {
+        self.registers[register_a as usize] = self.registers[register_a as usize] ^ self.registers[register_b as usize];
+        self.vf_reset();
+    }
+
+    fn vf_reset(&mut self) {
+        if self.options.quirks.vf_reset {
+            self.register_set_value(0xF, 0);
+        }
+    }
+
+    fn register_add(&mut self, register_a: u8, register_b: u8) {
+        let a = self.register_get_value(register_a);
+        let b = self.register_get_value(register_b);
+        let (result, overflows) = a.overflowing_add(b);
+        // the flag goes last, so that it wins when VF is the target
+        self.register_set_value(register_a, result);
+        self.register_set_value(0xF, if overflows { 1 } else { 0 });
+    }
+
+    /// Sets the target register to `register_a - register_b`, for both 8XY5 and 8XY7.
+    fn register_subtract(&mut self, target: u8, register_a: u8, register_b: u8) {
+        let a = self.register_get_value(register_a);
+        let b = self.register_get_value(register_b);
+        let (result, overflows) = a.overflowing_sub(b);
+        self.register_set_value(target, result);
+        self.register_set_value(0xF, if overflows { 0 } else { 1 });
+    }
+
+    fn register_left_shift(&mut self, register_a: u8, register_b: u8) {
+        if !self.options.quirks.shifting {
+            self.register_set(register_a, register_b);
+        }
+
+        let a = self.register_get_value(register_a);
+        self.register_set_value(register_a, self.register_get_value(register_a) << 1);
+        if a >= 128 {
+            self.register_set_value(0xF, 1);
+        } else {
+            self.register_set_value(0xF, 0);
+        }
+    }
+
+    #[allow(clippy::manual_is_multiple_of)]
+    fn register_right_shift(&mut self, register_a: u8, register_b: u8) {
+        if !self.options.quirks.shifting {
+            self.register_set(register_a, register_b);
+        }
+
+        let a = self.register_get_value(register_a);
+        self.register_set_value(register_a, self.register_get_value(register_a) >> 1);
+        if a % 2 == 0 {
+            self.register_set_value(0xF, 0);
+        } else {
+            self.register_set_value(0xF, 1);
+        }
+    }
+
+    fn is_key_pressed(&self, key: u8) -> bool {
+        return self.keypad & (1 << (key & 0xF)) > 0;
+    }
+
+    fn skip_if_key_is_pressed(&mut self, register: u8) {
+        let value = self.register_get_value(register);
+        if self.is_key_pressed(value) {
+            self.pc = self.pc.wrapping_add(2);
+        }
+    }
+
+    fn skip_if_key_is_not_pressed(&mut self, register: u8) {
+        let value = self.register_get_value(register);
+        if !self.is_key_pressed(value) {
+            self.pc = self.pc.wrapping_add(2);
+        }
+    }
+
+    fn draw(&mut self, x_register: u8, y_register: u8, height: u8) {
+        let x = (self.register_get_value(x_register) % 64) as usize;
+        let y = (self.register_get_value(y_register) % 32) as usize;
+
+        // set VF register to 0 until any pixel become 0
+        self.register_set_value(0xF, 0);
+
+        for h in 0..(height as usize) {
+            let row = match self.wrap(y + h, self.display[0].len()) {
+                Some(row) => row,
+                None => break,
+            };
+            let address = (self.i as usize + h) % self.ram.len();
+            if let Some(sanitizer) = self.sanitizer.as_mut() {
+                sanitizer.sprite_read(self.instruction_address, address);
+            }
+            let sprite_row = self.ram[address];
+            let display_row = self.get_display_row(x, row);
+            let (new_row, collision) = self.draw_sprite_row(sprite_row, display_row);
+            if collision {
+                self.register_set_value(0xF, 1);
+            }
+            self.set_display_row(x, row, new_row);
+        }
+
+        if self.options.quirks.display_wait {
+            self.vblank_wait = true;
+        }
+    }
+
+    /// Maps a coordinate past the screen edge back onto the screen, unless sprites are clipped.
+    fn wrap(&self, coordinate: usize, size: usize) -> Option<usize> {
+        if coordinate < size {
+            return Some(coordinate);
+        }
+        if self.options.quirks.clipping {
+            return None;
+        }
+        return Some(coordinate % size);
+    }
+
+    fn draw_sprite_row(&self, sprite_row: u8, display_row: u8) -> (u8, bool) {
+        let new_row = sprite_row ^ display_row;
+        let turned_off_pixels = display_row & sprite_row;
+        return (new_row, turned_off_pixels > 0);
+    }
+
+    #[allow(clippy::needless_range_loop)]
+    fn get_display_row(&mut self, x: usize, y: usize) -> u8 {
+        let mut row = [false; 8];
+        for i in 0..(row.len()) {
+            if let Some(column) = self.wrap(x + i, self.display.len()) {
+                row[i] = self.display[column][y];
+            } else {
+                row[i] = false;
+            }
+        }
+        return u8::from_bit_array(row);
+    }
+
+    #[allow(clippy::needless_range_loop)]
+    fn set_display_row(&mut self, x: usize, y: usize, row: u8) {
+        let bits = row.to_bit_array();
+        for bit in 0..(bits.len()) {
+            if let Some(column) = self.wrap(x + bit, self.display.len()) {
+                self.display[column][y] = bits[bit];
+            }
+        }
+    }
+
+    fn register_set_value_to_delay_timer(&mut self, register: u8) {
+        self.register_set_value(register, self.delay_timer);
+    }
+
+    fn random(&mut self, register: u8, value: u8) {
+        let random = self.random.next_byte(&self.ram);
+        self.register_set_value(register, random & value);
+    }
+
+    fn set_delay_timer(&mut self, register: u8) {
+        let (delay, sound) = (self.delay_timer, self.sound_timer);
+        self.delay_timer = self.register_get_value(register);
+        self.timers_changed(delay, sound);
+    }
+
+    fn set_sound_timer(&mut self, register: u8) {
+        let (delay, sound) = (self.delay_timer, self.sound_timer);
+        self.sound_timer = self.register_get_value(register);
+        self.timers_changed(delay, sound);
+    }
+
+    fn tick_timers(&mut self) {
+        let (delay, sound) = (self.delay_timer, self.sound_timer);
+        self.delay_timer = self.delay_timer.saturating_sub(1);
+        self.sound_timer = self.sound_timer.saturating_sub(1);
+        self.timers_changed(delay, sound);
+    }
+
+    /// Tells observers about timers that started or stopped since they were `delay` and `sound`.
+    fn timers_changed(&mut self, delay: u8, sound: u8) {
+        for (timer, before, now) in [(Timer::Delay, delay, self.delay_timer), (Timer::Sound, sound, self.sound_timer)] {
+            if (before > 0) != (now > 0) {
+                self.observers.each(|observer| observer.timer_changed(timer, now > 0));
+            }
+        }
+    }
+
+    /// Reads the keypad once per frame, from the movie being played back or from the input.
+    fn latch_keypad(&mut self) {
+        self.previous_keypad = self.keypad;
+        if let Some(MovieSession::Playing(player)) = self.movie.as_mut() {
+            if let Some(keypad) = player.next_keypad() {
+                self.keypad = keypad;
+                return;
+            }
+            info!("Movie playback finished after {} frames", player.frame());
+            self.movie = None;
+        }
+
+        self.keypad = (0..16u8)
+            .filter(|key| self.input.is_key_pressed(*key))
+            .fold(0, |keypad, key| keypad | (1 << key));
+    }
+
+    fn update_movie(&mut self) -> Result<(), String> {
+        let state_hash = match self.movie {
+            Some(_) => self.save_state().hash(),
+            None => return Ok(()),
+        };
+
+        match self.movie.as_mut().unwrap() {
+            #[cfg(feature = "std")]
+            MovieSession::Recording(recorder) => {
+                recorder.record(self.keypad, state_hash).map_err(|e| e.to_string())?;
+            }
+            MovieSession::Playing(player) => {
+                if let Some(frame) = player.verify(state_hash) {
+                    warn!("Movie playback diverged at frame {}", frame);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn add_to_index(&mut self, register: u8) {
+        let value = self.register_get_value(register) as u16;
+        let (i, overflow) = self.i.overflowing_add(value);
+        self.i = i;
+        if overflow {
+            self.register_set_value(0xF, 1)
+        } else {
+            // TODO: it's unclear if I have to reset to zero in case of non-overflow.
+            // self.register_set_value(0xF, 0)
+        }
+    }
+
+    fn set_index_register_to_font(&mut self, register_font: u8) {
+        let f = self.register_get_value(register_font) & 0xF;
+        debug!("INDEX_SET_FONT {}", f);
+        self.set_index_register(self.options.font_address + f as u16 * 5);
+    }
+
+    fn decimal_conversion(&mut self, register: u8) -> Result<(), String> {
+        let value = self.register_get_value(register);
+        let units = value % 10;
+        let tens = (value / 10) % 10;
+        let hundreds = value / 100;
+        self.write_ram(self.i as usize, hundreds)?;
+        self.write_ram(self.i as usize + 1, tens)?;
+        self.write_ram(self.i as usize + 2, units)?;
+        Ok(())
+    }
+
+    fn ram_store(&mut self, value: u8) -> Result<(), String> {
+        for x in 0..=(value as usize) {
+            self.write_ram(self.i as usize + x, self.register_get_value(x as u8))?;
+        }
+        if self.options.quirks.memory {
+            self.i = self.i.wrapping_add(value as u16 + 1);
+        }
+        Ok(())
+    }
+
+    fn ram_load(&mut self, value: u8) -> Result<(), String> {
+        for x in 0..=(value as usize) {
+            let address = self.i as usize + x;
+            if let Some(sanitizer) = self.sanitizer.as_mut() {
+                sanitizer.data_read(self.instruction_address, address);
+            }
+            let value = self.read_ram(address)?;
+            self.register_set_value(x as u8, value);
+        }
+        if self.options.quirks.memory {
+            self.i = self.i.wrapping_add(value as u16 + 1);
+        }
+        Ok(())
+    }
+
+    /// FX0A waits until a key is pressed and released again, as on the COSMAC VIP.
+    fn get_key(&mut self, register: u8) {
+        let released = self.previous_keypad & !self.keypad;
+        if released == 0 {
+            self.pc = self.pc.wrapping_sub(2);
+            return;
+        }
+
+        // consume the release, so that the next FX0A waits for another key
+        self.previous_keypad = self.keypad;
+        self.register_set_value(register, released.trailing_zeros() as u8);
+    }
+
+    /// Runs frames in real time until the program fails or exits, or a `Control` stops it.
+    /// Hosts without `std` call `run_frame` from their own 60 Hz tick instead.
+    #[cfg(feature = "std")]
+    pub fn execute(&mut self) -> StopReason {
+        loop {
+            let frame_start = Instant::now();
+            let commands: Vec<Command> = match &self.commands {
+                Some((_, receiver)) => receiver.try_iter().collect(),
+                None => Vec::new(),
+            };
+            for command in commands {
+                match command {
+                    Command::Reset => self.reset(),
+                    Command::LoadRom(rom) => {
+                        self.power_on();
+                        self.load_rom_bytes(rom);
+                    }
+                    Command::Configure(configure) => configure(self),
+                    Command::Stop => {
+                        self.observers.each(|observer| observer.halted(&StopReason::Quit));
+                        return StopReason::Quit;
+                    }
+                }
+            }
+
+            if self.input.is_rewind_pressed() {
+                self.rewind_frame();
+            } else if !self.paused.load(Ordering::Relaxed) || self.input.take_step() {
+                if let Err(e) = self.run_frame() {
+                    return StopReason::Error(e);
+                }
+                if let Some(reason) = self.take_trap() {
+                    warn!("Paused: {}", reason);
+                    self.paused.store(true, Ordering::Relaxed);
+                }
+                if self.exited {
+                    return StopReason::Exit;
+                }
+            }
+
+            let elapsed = frame_start.elapsed();
+            if elapsed < FRAME_DURATION {
+                thread::sleep(FRAME_DURATION - elapsed);
+            }
+        }
+    }
+
+    /// Runs one 60 Hz frame worth of instructions and records the resulting state for rewinding.
+    pub fn run_frame(&mut self) -> Result<(), String> {
+        self.run_frame_for(self.options.instructions_per_frame)?;
+        Ok(())
+    }
+
+    /// Runs a frame of at most `limit` instructions, fewer if the program waits for the vertical
+    /// blank, exits or trips a trap, and returns how many ran.
+    pub fn run_frame_for(&mut self, limit: u32) -> Result<u32, String> {
+        self.latch_keypad();
+        self.tick_timers();
+        self.vblank_wait = false;
+        let mut executed = 0;
+        while executed < limit && !self.exited {
+            self.step()?;
+            executed += 1;
+            if self.vblank_wait || self.exited || self.trap.is_some() {
+                break;
+            }
+        }
+        self.present();
+        self.update_sound();
+        self.update_movie()?;
+
+        if let Some(mut rewind) = self.rewind.take() {
+            rewind.push(&self.save_state());
+            self.rewind = Some(rewind);
+        }
+
+        return Ok(executed);
+    }
+
+    /// Steps one frame back in time, if the rewind buffer still has frames to give.
+    pub fn rewind_frame(&mut self) {
+        if self.movie.is_some() {
+            // going back in time would desync the movie from the frames it has seen
+            return;
+        }
+
+        let state = match self.rewind.as_mut().and_then(|rewind| rewind.pop()) {
+            Some(state) => state,
+            None => return,
+        };
+
+        debug!("REWIND {} frames left", self.rewind.as_ref().unwrap().len());
+        self.load_state(&state);
+        self.present();
+    }
+
+    /// The buzzer sounds for as long as the sound timer is running.
+    fn update_sound(&mut self) {
+        let on = self.sound_timer > 0;
+        if on != self.sound_on {
+            self.sound_on = on;
+            self.display_output.sound(on);
+        }
+    }
+
+    /// Shows the screen at the end of a frame, if it changed during the frame.
+    fn present(&mut self) {
+        if self.display_dirty {
+            self.display_dirty = false;
+            self.display_output.draw(self.display);
+        }
+    }
+
+    pub fn step(&mut self) -> Result<(), String> {
+        if self.exited {
+            return Ok(());
+        }
+        let result = self.run_instruction();
+        let reason = match &result {
+            Err(e) => Some(StopReason::Error(e.clone())),
+            Ok(()) if self.exited => Some(StopReason::Exit),
+            Ok(()) => None,
+        };
+        if let Some(reason) = reason {
+            self.observers.each(|observer| observer.halted(&reason));
+        }
+        return result;
+    }
+
+    fn run_instruction(&mut self) -> Result<(), String> {
+        // read the instruction pointed from the pc:
+        let pc = self.pc;
+        let instruction = self.fetch_instruction()?;
+        self.last_instruction = Some(instruction);
+        self.instruction_address = pc;
+        if let Some(sanitizer) = self.sanitizer.as_mut() {
+            sanitizer.executed(pc);
+        }
+
+        let i = self.i;
+        self.observers.each(|observer| observer.before_instruction(pc, instruction));
+        self.execute_instruction(instruction)?;
+        if let Some(sanitizer) = self.sanitizer.as_mut().filter(|_| self.i != i) {
+            sanitizer.index(pc, self.i);
+        }
+        self.observers.each(|observer| observer.after_instruction(pc, instruction));
+        Ok(())
+    }
+
+    /// The screen changed: it is shown at the end of the frame, observers hear of it now.
+    fn screen_changed(&mut self) {
+        self.display_dirty = true;
+        let display = &self.display;
+        self.observers.each(|observer| observer.screen_changed(display));
+    }
+
+    fn execute_instruction(&mut self, instruction: Instruction) -> Result<(), String> {
+        match instruction.first_nibble {
+            0x0 => {
+                if instruction.byte_sum_3() == 0x0E0 {
+                    self.clear_screen();
+                    self.screen_changed();
+                } else if instruction.byte_sum_3() == 0x0EE {
+                    self.subroutine_return()?;
+                } else if instruction.byte_sum_3() == 0x0FD {
+                    self.exited = true;
+                }
+            }
+
+            0x1 => {
+                self.jump(instruction.byte_sum_3());
+            }
+
+            0x2 => {
+                self.call_subroutine(instruction.byte_sum_3());
+            }
+
+            0x3 => {
+                self.skip_if_equals(self.register_get_value(instruction.second_nibble), instruction.byte_sum_2());
+            }
+
+            0x4 => {
+                self.skip_if_not_equals(self.register_get_value(instruction.second_nibble), instruction.byte_sum_2());
+            }
+
+            0x5 => {
+                self.skip_if_equals(self.register_get_value(instruction.second_nibble), self.register_get_value(instruction.third_nibble));
+            }
+
+            0x6 => {
+                self.register_set_value(instruction.second_nibble, instruction.byte_sum_2());
+            }
+
+            0x7 => {
+                self.register_add_value(instruction.second_nibble, instruction.byte_sum_2());
+            }
+
+            0x8 => {
+                match instruction.fourth_nibble {
+                    0x0 => {
+                        self.register_set(instruction.second_nibble, instruction.third_nibble);
+                    }
+                    0x1 => {
+                        self.register_or(instruction.second_nibble, instruction.third_nibble);
+                    }
+                    0x2 => {
+                        self.register_and(instruction.second_nibble, instruction.third_nibble);
+                    }
+                    0x3 => {
+                        self.register_xor(instruction.second_nibble, instruction.third_nibble);
+                    }
+                    0x4 => {
+                        self.register_add(instruction.second_nibble, instruction.third_nibble);
+                    }
+                    0x5 => {
+                        self.register_subtract(instruction.second_nibble, instruction.second_nibble, instruction.third_nibble);
+                    }
+                    0x6 => {
+                        self.register_right_shift(instruction.second_nibble, instruction.third_nibble);
+                    }
+                    0x7 => {
+                        self.register_subtract(instruction.second_nibble, instruction.third_nibble, instruction.second_nibble);
+                    }
+                    0xE => {
+                        self.register_left_shift(instruction.second_nibble, instruction.third_nibble);
+                    }
+                    _ => {}
+                }
+            }
+
+            0x9 => {
+                self.skip_if_not_equals(self.register_get_value(instruction.second_nibble), self.register_get_value(instruction.third_nibble));
+            }
+
+            0xA => {
+                self.set_index_register(instruction.byte_sum_3());
+            }
+
+            0xB => {
+                self.jump_with_offset(instruction.byte_sum_3());
+            }
+
+            0xC => {
+                self.random(instruction.second_nibble, instruction.byte_sum_2());
+            }
+
+            0xD => {
+                self.draw(instruction.second_nibble, instruction.third_nibble, instruction.fourth_nibble);
+                self.screen_changed();
+            }
+
+            0xE => {
+                if instruction.byte_sum_2() == 0x9E {
+                    self.skip_if_key_is_pressed(instruction.second_nibble)
+                } else if instruction.byte_sum_2() == 0xA1 {
+                    self.skip_if_key_is_not_pressed(instruction.second_nibble)
+                }
+            }
+
+            0xF => {
+                if instruction.byte_sum_2() == 0x07 {
+                    self.register_set_value_to_delay_timer(instruction.second_nibble);
+                } else if instruction.byte_sum_2() == 0x15 {
+                    self.set_delay_timer(instruction.second_nibble);
+                } else if instruction.byte_sum_2() == 0x18 {
+                    self.set_sound_timer(instruction.second_nibble);
+                } else if instruction.byte_sum_2() == 0x1E {
+                    self.add_to_index(instruction.second_nibble);
+                } else if instruction.byte_sum_2() == 0x0A {
+                    self.get_key(instruction.second_nibble);
+                } else if instruction.byte_sum_2() == 0x29 {
+                    self.set_index_register_to_font(instruction.second_nibble);
+                } else if instruction.byte_sum_2() == 0x33 {
+                    self.decimal_conversion(instruction.second_nibble)?;
+                } else if instruction.byte_sum_2() == 0x55 {
+                    self.ram_store(instruction.second_nibble)?;
+                } else if instruction.byte_sum_2() == 0x65 {
+                    self.ram_load(instruction.second_nibble)?;
+                }
+            }
+
+            _ => {
+                return Err(format!("Unknown instruction: {}", instruction.first_nibble));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+trait BitArray<T> {
+    fn to_bit_array(&self) -> [bool; 8];
+
+    fn from_bit_array(bits: [bool; 8]) -> T;
+}
+
+impl BitArray<u8> for u8 {
+    fn to_bit_array(&self) -> [bool; 8] {
+        let mut result: [bool; 8] = [false; 8];
+        for bit in 0..result.len() {
+            result[result.len() - 1 - bit] = ((*self) & (1 << bit)) > 0;
+        }
+        return result;
+    }
+
+    fn from_bit_array(bits: [bool; 8]) -> u8 {
+        let mut result: u8 = 0;
+        for bit in 0..bits.len() {
+            if bits[bits.len() - 1 - bit] {
+                result += 1 << bit
+            }
+        }
+        return result;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::thread;
+
+    use crate::basic::{DummyInput, NullDisplay};
+    use crate::cpu::{sha1, BitArray, Chip8, Display, Quirks, RomDatabase};
+    #[cfg(feature = "std")]
+    use crate::cpu::Input;
+
+    #[test]
+    #[allow(clippy::bool_assert_comparison)]
+    fn draw_sprite_row() {
+        let input = DummyInput {};
+        let display = NullDisplay {};
+        let cpu = Chip8::new(Box::new(input), Box::new(display));
+        let (row, collision) = cpu.draw_sprite_row(0x1, 0x0);
+        assert_eq!(row, 0x1);
+        assert_eq!(collision, false);
+
+        let (row, collision) = cpu.draw_sprite_row(0x2, 0x6);
+        assert_eq!(row, 0x4);
+        assert_eq!(collision, true);
+    }
+
+    #[test]
+    fn runs_code_at_the_top_of_64k() {
+        let mut cpu = Chip8::builder(Box::new(DummyInput {}), Box::new(NullDisplay {})).memory_size(0x10000).build().unwrap();
+        // 3000: skip, V0 is 0, past the end of memory
+        cpu.ram[0xFFFC..].copy_from_slice(&[0x30, 0x00, 0x60, 0x05]);
+        cpu.debugger().set_pc(0xFFFC).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(0, cpu.pc);
+
+        // 6005: V0 = 5, the last instruction in memory
+        cpu.debugger().set_pc(0xFFFE).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(5, cpu.registers[0]);
+        assert_eq!(0, cpu.pc);
+
+        // F00A: wait for a key, at the last instruction in memory
+        cpu.ram[0xFFFE..].copy_from_slice(&[0xF0, 0x0A]);
+        cpu.debugger().set_pc(0xFFFE).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(0xFFFE, cpu.pc);
+
+        cpu.debugger().set_pc(0xFFFF).unwrap_err();
+        cpu.pc = 0xFFFF;
+        assert!(cpu.step().is_err());
+    }
+
+    #[test]
+    fn memory_past_the_end_is_an_error() {
+        let mut cpu = Chip8::new(Box::new(DummyInput {}), Box::new(NullDisplay {}));
+        // AFFF: I = FFF, F165: load V0 and V1, the second from past the end of memory
+        cpu.load_rom_bytes(vec![0xAF, 0xFF, 0xF1, 0x65]);
+        cpu.step().unwrap();
+        assert_eq!(Err(String::from("Read outside memory at 0x1000")), cpu.step());
+
+        // with the memory quirk I wraps around at the end of 64 KB instead of overflowing
+        let mut cpu = Chip8::builder(Box::new(DummyInput {}), Box::new(NullDisplay {})).memory_size(0x10000).build().unwrap();
+        cpu.set_quirks(Quirks::chip8());
+        cpu.i = 0xFFFF;
+        cpu.ram_load(0).unwrap();
+        assert_eq!(0, cpu.i);
+        cpu.i = 0xFFFF;
+        cpu.ram_store(0).unwrap();
+        assert_eq!(0, cpu.i);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn loading_a_directory_is_an_error() {
+        let mut cpu = Chip8::new(Box::new(DummyInput {}), Box::new(NullDisplay {}));
+        assert!(cpu.load_rom_file(String::from(env!("CARGO_MANIFEST_DIR"))).is_err());
+    }
+
+    #[test]
+    fn rewind_length_must_fit_in_frames() {
+        let mut cpu = Chip8::new(Box::new(DummyInput {}), Box::new(NullDisplay {}));
+        assert!(cpu.enable_rewind(u32::MAX).is_err());
+        assert!(cpu.enable_rewind(u32::MAX / 60).is_ok());
+    }
+
+    #[test]
+    fn rewind_restores_previous_frame() {
+        let mut cpu = Chip8::new(Box::new(DummyInput {}), Box::new(NullDisplay {}));
+        cpu.enable_rewind(1).unwrap();
+        // 7001: V0 += 1, 1200: jump back to start
+        cpu.load_rom_bytes(vec![0x70, 0x01, 0x12, 0x00]);
+
+        cpu.run_frame().unwrap();
+        let first_frame = cpu.save_state();
+        cpu.run_frame().unwrap();
+        assert_eq!(8, cpu.registers[0]);
+
+        cpu.rewind_frame();
+        assert_eq!(first_frame, cpu.save_state());
+        assert_eq!(4, cpu.registers[0]);
+    }
+
+    #[test]
+    fn same_seed_gives_same_run() {
+        // C0FF: V0 = random, 7101: V1 += 1, F015: delay timer = V0, 1200: jump back to start
+        let rom = vec![0xC0, 0xFF, 0x71, 0x01, 0xF0, 0x15, 0x12, 0x00];
+        let mut first = Chip8::new(Box::new(DummyInput {}), Box::new(NullDisplay {}));
+        let mut second = Chip8::new(Box::new(DummyInput {}), Box::new(NullDisplay {}));
+        first.load_rom_bytes(rom.clone());
+        second.load_rom_bytes(rom);
+        first.set_seed(1234);
+        second.set_seed(1234);
+
+        for _ in 0..10 {
+            first.run_frame().unwrap();
+            second.run_frame().unwrap();
+            assert_eq!(first.save_state().hash(), second.save_state().hash());
+        }
+    }
+
+    /// A keypad the test presses keys on between frames.
+    #[cfg(feature = "std")]
+    #[derive(Clone, Default)]
+    struct SharedInput {
+        keypad: Arc<AtomicU32>,
+    }
+
+    #[cfg(feature = "std")]
+    impl Input for SharedInput {
+        fn is_key_pressed(&self, key: u8) -> bool {
+            return self.keypad.load(Ordering::Relaxed) & (1 << key) != 0;
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn movies_replay_key_waits_and_check_the_speed() {
+        let path = std::env::temp_dir().join(format!("chip8-movie-{}.txt", std::process::id()));
+        // F00A: wait for a key into V0, 7101: V1 += 1, 1202: loop
+        let rom = vec![0xF0, 0x0A, 0x71, 0x01, 0x12, 0x02];
+        let input = SharedInput::default();
+        let mut recording = Chip8::new(Box::new(input.clone()), Box::new(NullDisplay {}));
+        recording.load_rom_bytes(rom.clone());
+        recording.set_instructions_per_frame(5).unwrap();
+        recording.record_movie(path.to_str().unwrap().to_string()).unwrap();
+        for frame in 0..6 {
+            input.keypad.store(if frame == 2 { 1 << 7 } else { 0 }, Ordering::Relaxed);
+            recording.run_frame().unwrap();
+        }
+        assert_eq!(7, recording.registers[0]);
+
+        // nothing is pressed live, so only the movie can get past F00A
+        let mut playback = Chip8::new(Box::new(DummyInput {}), Box::new(NullDisplay {}));
+        playback.load_rom_bytes(rom.clone());
+        assert!(playback.play_movie(path.to_str().unwrap().to_string()).unwrap_err().contains("instructions per frame"));
+        playback.set_instructions_per_frame(5).unwrap();
+        playback.play_movie(path.to_str().unwrap().to_string()).unwrap();
+        for _ in 0..6 {
+            playback.run_frame().unwrap();
+        }
+        assert_eq!(recording.save_state(), playback.save_state());
+
+        let mut moved = Chip8::builder(Box::new(DummyInput {}), Box::new(NullDisplay {})).font_address(0x80).build().unwrap();
+        moved.load_rom_bytes(rom);
+        moved.set_instructions_per_frame(5).unwrap();
+        assert!(moved.play_movie(path.to_str().unwrap().to_string()).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    /// Counts the frames it is shown; clones share the count.
+    #[derive(Clone, Default)]
+    struct CountingDisplay {
+        frames: Arc<AtomicU32>,
+    }
+
+    impl CountingDisplay {
+        fn frames(&self) -> u32 {
+            return self.frames.load(Ordering::SeqCst);
+        }
+    }
+
+    impl Display for CountingDisplay {
+        fn draw(&self, _display: [[bool; 32]; 64]) {
+            self.frames.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn screen_is_shown_once_per_changed_frame() {
+        let display = CountingDisplay::default();
+        let mut cpu = Chip8::new(Box::new(DummyInput {}), Box::new(display.clone()));
+        cpu.set_instructions_per_frame(4).unwrap();
+        // 00E0: clear, D005 and D005: draw twice, 1206: loop on the jump
+        cpu.load_rom_bytes(vec![0x00, 0xE0, 0xD0, 0x05, 0xD0, 0x05, 0x12, 0x06]);
+
+        cpu.run_frame().unwrap();
+        assert_eq!(1, display.frames());
+        cpu.run_frame().unwrap();
+        assert_eq!(1, display.frames());
+    }
+
+    #[test]
+    fn display_can_be_swapped_on_another_thread() {
+        let first = CountingDisplay::default();
+        let mut cpu = Chip8::new(Box::new(DummyInput {}), Box::new(first.clone()));
+        // 00E0: clear, 1202: loop on the jump
+        cpu.load_rom_bytes(vec![0x00, 0xE0, 0x12, 0x02]);
+        cpu.run_frame().unwrap();
+
+        let second = CountingDisplay::default();
+        let handle = second.clone();
+        let mut cpu = thread::spawn(move || {
+            cpu.replace_display(Box::new(handle));
+            cpu.run_frame().unwrap();
+            return cpu;
+        }).join().unwrap();
+
+        // the new display is shown the current screen right away, the old one nothing more
+        assert_eq!(1, first.frames());
+        assert_eq!(1, second.frames());
+        cpu.run_frame().unwrap();
+        assert_eq!(1, second.frames());
+    }
+
+    #[test]
+    fn known_rom_gets_its_quirks_and_speed() {
+        let rom = vec![0x12, 0x00];
+        let database = RomDatabase::parse(&format!(
+            r#"[{{ "title": "Loop", "roms": {{ "{}": {{ "platforms": ["originalChip8"], "tickrate": 15 }} }} }}]"#,
+            sha1(&rom))).unwrap();
+
+        let mut cpu = Chip8::new(Box::new(DummyInput {}), Box::new(NullDisplay {}));
+        cpu.set_database(database);
+        cpu.load_rom_bytes(rom);
+
+        assert_eq!("Loop", cpu.rom_info().unwrap().title);
+        assert_eq!(Quirks::chip8(), cpu.quirks());
+        assert_eq!(15, cpu.instructions_per_frame());
+
+        cpu.load_rom_bytes(vec![0x00, 0xE0]);
+        assert!(cpu.rom_info().is_none());
+    }
+
+    #[test]
+    fn to_bit_array() {
+        let v: u8 = 0x85; // 10000101
+        let a = v.to_bit_array();
+
+        assert_eq!(a, [true, false, false, false, false, true, false, true]);
+    }
+
+    #[test]
+    fn from_bit_array() {
+        let v: u8 = u8::from_bit_array([true, false, false, false, false, true, false, true]); // 0x85
+        assert_eq!(v, 0x85);
+    }
+
+    #[test]
+    fn get_display_row() {
+        let mut cpu = Chip8::new(Box::new(DummyInput {}), Box::new(NullDisplay {}));
+        // 10000101 -> 0x85 -> 113
+        cpu.display[0][0] = true;
+        cpu.display[1][0] = false;
+        cpu.display[2][0] = false;
+        cpu.display[3][0] = false;
+        cpu.display[4][0] = false;
+        cpu.display[5][0] = true;
+        cpu.display[6][0] = false;
+        cpu.display[7][0] = true;
+
+        // 10000000 -> 128
+        cpu.display[60][0] = true;
+        cpu.display[61][0] = false;
+        cpu.display[62][0] = false;
+        cpu.display[63][0] = false;
+        // cpu.display[4][0] = false;
+        // cpu.display[5][0] = true;
+        // cpu.display[6][0] = false;
+        // cpu.display[7][0] = true;
+        let row = cpu.get_display_row(60, 0);
+        assert_eq!(128, row);
+    }
+}
@@ -0,0 +1,333 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+#[cfg(feature = "std")]
+use std::sync::Arc;
+#[cfg(feature = "std")]
+use std::sync::atomic::AtomicBool;
+#[cfg(feature = "std")]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::cpu::observer::Observers;
+use crate::cpu::{Chip8, Chip8Options, Display, Input, Quirks, Random, RandomAlgorithm};
+
+/// The hexadecimal digits 0 to F, five rows of a 4x5 sprite each.
+pub const FONT: [u8; 80] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0,   // 0
+    0x20, 0x60, 0x20, 0x20, 0x70,   // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0,   // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0,   // 3
+    0x90, 0x90, 0xF0, 0x10, 0x10,   // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0,   // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0,   // 6
+    0xF0, 0x10, 0x20, 0x40, 0x40,   // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0,   // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0,   // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90,   // A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0,   // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0,   // C
+    0xE0, 0x90, 0x90, 0x90, 0xE0,   // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0,   // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80,   // F
+];
+
+/// Where this emulator has always put the font.
+pub const DEFAULT_FONT_ADDRESS: u16 = 50;
+/// Where programs start on the COSMAC VIP and nearly every interpreter since.
+pub const DEFAULT_PROGRAM_START: u16 = 0x200;
+const DEFAULT_MEMORY_SIZE: usize = 4096;
+/// Addresses are 16 bits wide, so more memory could not be reached.
+pub const MAX_MEMORY_SIZE: usize = 0x10000;
+
+/// A machine the builder knows the quirks and memory size of. There is no XO-CHIP, whose
+/// opcodes are not implemented; its quirks and 64 KB are `quirks(Quirks::xochip())` and
+/// `memory_size(MAX_MEMORY_SIZE)`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Platform {
+    /// The COSMAC VIP interpreter, with 4 KB.
+    Chip8,
+    /// SUPER-CHIP 1.1, with 4 KB.
+    Schip,
+}
+
+impl Platform {
+    pub fn quirks(&self) -> Quirks {
+        return match self {
+            Platform::Chip8 => Quirks::chip8(),
+            Platform::Schip => Quirks::schip(),
+        };
+    }
+
+    pub fn memory_size(&self) -> usize {
+        return match self {
+            Platform::Chip8 | Platform::Schip => 4096,
+        };
+    }
+}
+
+impl FromStr for Platform {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Platform, String> {
+        return match name {
+            "chip8" => Ok(Platform::Chip8),
+            "schip" => Ok(Platform::Schip),
+            _ => Err(format!("Unknown platform: {}, expected chip8 or schip", name)),
+        };
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            Platform::Chip8 => write!(f, "chip8"),
+            Platform::Schip => write!(f, "schip"),
+        };
+    }
+}
+
+/// Sets up a machine before it starts. Quirks and memory size given explicitly win over the
+/// platform's, whatever the order of the calls. `build` checks the combination, so that an
+/// impossible layout is reported up front instead of corrupting memory later:
+///
+/// ```
+/// # #[cfg(feature = "std")] {
+/// use chrip8_core::basic::{ConsoleDisplay, DummyInput};
+/// use chrip8_core::cpu::{Chip8, Platform};
+///
+/// let chip8 = Chip8::builder(Box::new(DummyInput {}), Box::new(ConsoleDisplay {}))
+///     .platform(Platform::Schip)
+///     .font_address(0x50)
+///     .seed(1234)
+///     .instructions_per_frame(15)
+///     .build()
+///     .unwrap();
+/// # }
+/// ```
+pub struct Chip8Builder {
+    input: Box<dyn Input>,
+    display: Box<dyn Display>,
+    platform: Option<Platform>,
+    quirks: Option<Quirks>,
+    memory_size: Option<usize>,
+    font: [u8; 80],
+    font_address: u16,
+    program_start: u16,
+    random_algorithm: RandomAlgorithm,
+    seed: Option<u64>,
+    instructions_per_frame: u32,
+    registers: [u8; 16],
+}
+
+impl Chip8Builder {
+    pub fn new(input: Box<dyn Input>, display: Box<dyn Display>) -> Chip8Builder {
+        return Chip8Builder {
+            input,
+            display,
+            platform: None,
+            quirks: None,
+            memory_size: None,
+            font: FONT,
+            font_address: DEFAULT_FONT_ADDRESS,
+            program_start: DEFAULT_PROGRAM_START,
+            random_algorithm: RandomAlgorithm::Xorshift,
+            seed: None,
+            instructions_per_frame: 8,
+            registers: [0; 16],
+        };
+    }
+
+    /// Takes the quirks and memory size of a platform.
+    pub fn platform(mut self, platform: Platform) -> Chip8Builder {
+        self.platform = Some(platform);
+        return self;
+    }
+
+    pub fn quirks(mut self, quirks: Quirks) -> Chip8Builder {
+        self.quirks = Some(quirks);
+        return self;
+    }
+
+    /// RAM in bytes, 4096 by default and at most `MAX_MEMORY_SIZE`.
+    pub fn memory_size(mut self, memory_size: usize) -> Chip8Builder {
+        self.memory_size = Some(memory_size);
+        return self;
+    }
+
+    /// Sprites for the digits 0 to F, five bytes each, used by FX29.
+    pub fn font(mut self, font: [u8; 80]) -> Chip8Builder {
+        self.font = font;
+        return self;
+    }
+
+    pub fn font_address(mut self, address: u16) -> Chip8Builder {
+        self.font_address = address;
+        return self;
+    }
+
+    /// Where ROMs are loaded and execution starts.
+    pub fn program_start(mut self, address: u16) -> Chip8Builder {
+        self.program_start = address;
+        return self;
+    }
+
+    pub fn random_algorithm(mut self, algorithm: RandomAlgorithm) -> Chip8Builder {
+        self.random_algorithm = algorithm;
+        return self;
+    }
+
+    /// Seeds CXNN, so that runs can be reproduced. Without a seed runs differ, or, without
+    /// `std`, the seed is 0.
+    pub fn seed(mut self, seed: u64) -> Chip8Builder {
+        self.seed = Some(seed);
+        return self;
+    }
+
+    pub fn instructions_per_frame(mut self, instructions_per_frame: u32) -> Chip8Builder {
+        self.instructions_per_frame = instructions_per_frame;
+        return self;
+    }
+
+    /// V0 to VF when the machine starts.
+    pub fn registers(mut self, registers: [u8; 16]) -> Chip8Builder {
+        self.registers = registers;
+        return self;
+    }
+
+    pub fn build(self) -> Result<Chip8, String> {
+        let memory_size = self.memory_size.or(self.platform.map(|platform| platform.memory_size())).unwrap_or(DEFAULT_MEMORY_SIZE);
+        let quirks = self.quirks.or(self.platform.map(|platform| platform.quirks())).unwrap_or_default();
+
+        if memory_size > MAX_MEMORY_SIZE {
+            return Err(format!("Memory of {} bytes is more than 16-bit addresses reach", memory_size));
+        }
+        let program_start = self.program_start as usize;
+        if program_start + 2 > memory_size {
+            return Err(format!("Program start {:#05x} leaves no room for a program in {} bytes of memory", program_start, memory_size));
+        }
+        let font_end = self.font_address as usize + self.font.len();
+        if font_end > program_start {
+            return Err(format!("Font at {:#05x}-{:#05x} overlaps the program at {:#05x}", self.font_address, font_end - 1, program_start));
+        }
+        if self.instructions_per_frame == 0 {
+            return Err(String::from("At least one instruction has to run per frame"));
+        }
+
+        let mut ram = vec![0x0; memory_size];
+        ram[self.font_address as usize..font_end].copy_from_slice(&self.font);
+
+        return Ok(Chip8 {
+            ram,
+            display: [[false; 32]; 64],
+            pc: self.program_start,
+            i: 0,
+            stack: Vec::new(),
+            delay_timer: 0,
+            sound_timer: 0,
+            registers: self.registers,
+            keypad: 0,
+            previous_keypad: 0,
+            random: Random::new(self.random_algorithm, self.seed.unwrap_or_else(default_seed)),
+            rom_hash: 0,
+            rom_info: None,
+            last_instruction: None,
+            instruction_address: self.program_start,
+            vblank_wait: false,
+            exited: false,
+            trap: None,
+            display_dirty: false,
+            sound_on: false,
+            options: Chip8Options {
+                quirks,
+                instructions_per_frame: self.instructions_per_frame,
+                database: None,
+                font: self.font,
+                font_address: self.font_address,
+                program_start: self.program_start,
+                registers: self.registers,
+                protection: None,
+            },
+            rewind: None,
+            movie: None,
+            observers: Observers::default(),
+            sanitizer: None,
+            rom: Vec::new(),
+            #[cfg(feature = "std")]
+            commands: None,
+            #[cfg(feature = "std")]
+            paused: Arc::new(AtomicBool::new(false)),
+            input: self.input,
+            display_output: self.display,
+        });
+    }
+}
+
+/// Runs differ unless a seed is set explicitly; without a clock the host has to bring its
+/// own entropy.
+#[cfg(feature = "std")]
+fn default_seed() -> u64 {
+    return SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_nanos() as u64).unwrap_or(0);
+}
+
+#[cfg(not(feature = "std"))]
+fn default_seed() -> u64 {
+    return 0;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::basic::{DummyInput, NullDisplay};
+    use crate::cpu::builder::{Chip8Builder, Platform, FONT, MAX_MEMORY_SIZE};
+    use crate::cpu::Quirks;
+
+    fn builder() -> Chip8Builder {
+        return Chip8Builder::new(Box::new(DummyInput {}), Box::new(NullDisplay {}));
+    }
+
+    #[test]
+    fn explicit_settings_win_over_the_platform() {
+        let chip8 = builder().platform(Platform::Schip).build().unwrap();
+        assert_eq!(4096, chip8.ram.len());
+        assert_eq!(Quirks::schip(), chip8.quirks());
+
+        let chip8 = builder().quirks(Quirks::schip()).memory_size(8192).platform(Platform::Chip8).build().unwrap();
+        assert_eq!(8192, chip8.ram.len());
+        assert_eq!(Quirks::schip(), chip8.quirks());
+    }
+
+    #[test]
+    fn font_and_program_go_where_asked() {
+        let mut chip8 = builder().font_address(0x50).program_start(0x600).registers([3; 16]).build().unwrap();
+        assert_eq!(FONT[..], chip8.ram[0x50..0xA0]);
+
+        // F029: I = sprite of the digit in V0
+        chip8.load_rom_bytes(vec![0xF0, 0x29]);
+        assert_eq!(0x600, chip8.pc);
+        chip8.step().unwrap();
+        assert_eq!(0x50 + 3 * 5, chip8.i);
+    }
+
+    #[test]
+    fn rejects_impossible_layouts() {
+        assert!(builder().memory_size(MAX_MEMORY_SIZE + 1).build().is_err());
+        assert!(builder().quirks(Quirks::xochip()).memory_size(MAX_MEMORY_SIZE).build().is_ok());
+        assert!(builder().memory_size(0x200).build().is_err());
+        assert!(builder().font_address(0x1D0).build().is_err());
+        assert!(builder().font_address(0x700).program_start(0x600).build().is_err());
+        assert!(builder().instructions_per_frame(0).build().is_err());
+        assert!(builder().build().unwrap().set_instructions_per_frame(0).is_err());
+        assert!(builder().font_address(0).program_start(0x50).memory_size(0x52).build().is_ok());
+    }
+
+    #[test]
+    fn platform_names() {
+        assert_eq!(Ok(Platform::Chip8), "chip8".parse());
+        assert!("xochip".parse::<Platform>().is_err());
+        assert_eq!("schip", Platform::Schip.to_string());
+        assert!("megachip".parse::<Platform>().is_err());
+    }
+}
@@ -0,0 +1,158 @@
+use alloc::string::String;
+#[cfg(feature = "std")]
+use alloc::boxed::Box;
+#[cfg(feature = "std")]
+use alloc::vec::Vec;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::sync::Arc;
+#[cfg(feature = "std")]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "std")]
+use std::sync::mpsc::Sender;
+
+#[cfg(feature = "std")]
+use crate::cpu::Chip8;
+
+/// Why `Chip8::execute` returned.
+#[derive(Clone, Debug, PartialEq)]
+pub enum StopReason {
+    /// The program could not go on, e.g. it jumped out of memory.
+    Error(String),
+    /// The program ended itself with 00FD.
+    Exit,
+    /// `Control::stop` was called, usually because the user quit.
+    Quit,
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            StopReason::Error(e) => write!(f, "{}", e),
+            StopReason::Exit => write!(f, "the program exited"),
+            StopReason::Quit => write!(f, "quit"),
+        };
+    }
+}
+
+/// What a `Control` asks the running machine to do, picked up at the start of the next frame.
+/// Pausing is a shared flag instead, so that it can be read back.
+#[cfg(feature = "std")]
+pub(super) enum Command {
+    Reset,
+    LoadRom(Vec<u8>),
+    Configure(Box<dyn FnOnce(&mut Chip8) + Send>),
+    Stop,
+}
+
+/// Steers a machine running `execute` on another thread; clones steer the same machine.
+/// Commands sent after the machine stopped are ignored.
+#[cfg(feature = "std")]
+#[derive(Clone)]
+pub struct Control {
+    pub(super) commands: Sender<Command>,
+    pub(super) paused: Arc<AtomicBool>,
+}
+
+#[cfg(feature = "std")]
+impl Control {
+    /// Holds the machine still until `resume`, on top of any pause the input asks for.
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::Relaxed);
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::Relaxed);
+    }
+
+    /// Whether `pause` was called, or a trap on protected memory paused the machine.
+    pub fn is_paused(&self) -> bool {
+        return self.paused.load(Ordering::Relaxed);
+    }
+
+    /// Starts the current ROM over, as if the machine had just been switched on.
+    pub fn reset(&self) {
+        self.send(Command::Reset);
+    }
+
+    /// Switches the machine off and on again with another ROM.
+    pub fn load_rom(&self, rom: Vec<u8>) {
+        self.send(Command::LoadRom(rom));
+    }
+
+    /// Hands the machine to `configure` on its own thread, after the commands sent before, e.g.
+    /// to apply the settings of a ROM sent with `load_rom`.
+    pub fn configure(&self, configure: impl FnOnce(&mut Chip8) + Send + 'static) {
+        self.send(Command::Configure(Box::new(configure)));
+    }
+
+    /// Makes `execute` return `StopReason::Quit`.
+    pub fn stop(&self) {
+        self.send(Command::Stop);
+    }
+
+    fn send(&self, command: Command) {
+        // the machine has stopped already, so there is nothing left to steer
+        let _ = self.commands.send(command);
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::thread;
+
+    use crate::basic::{DummyInput, NullDisplay};
+    use crate::cpu::{Chip8, StopReason};
+
+    #[test]
+    fn stop_ends_the_machine_thread() {
+        let mut chip8 = Chip8::new(Box::new(DummyInput {}), Box::new(NullDisplay {}));
+        // 1200: jump to itself
+        chip8.load_rom_bytes(vec![0x12, 0x00]);
+        let control = chip8.control();
+        let machine = thread::spawn(move || chip8.execute());
+
+        control.pause();
+        control.stop();
+        assert_eq!(StopReason::Quit, machine.join().unwrap());
+        // nobody is listening any more
+        control.resume();
+    }
+
+    #[test]
+    fn exit_and_errors_are_reported() {
+        let mut chip8 = Chip8::new(Box::new(DummyInput {}), Box::new(NullDisplay {}));
+        // 6005: V0 = 5, 00FD: exit, 6006: never runs
+        chip8.load_rom_bytes(vec![0x60, 0x05, 0x00, 0xFD, 0x60, 0x06]);
+        assert_eq!(StopReason::Exit, chip8.execute());
+        assert!(chip8.has_exited());
+        assert_eq!(5, chip8.registers()[0]);
+
+        // 00EE: return with an empty stack, at full speed
+        chip8.control().load_rom(vec![0x00, 0xEE]);
+        chip8.control().configure(|chip8| chip8.set_instructions_per_frame(100).unwrap());
+        assert_eq!(StopReason::Error(String::from("Return from a subroutine with an empty stack")), chip8.execute());
+        assert_eq!(100, chip8.instructions_per_frame());
+    }
+
+    #[test]
+    fn reset_starts_the_rom_over() {
+        let mut chip8 = Chip8::builder(Box::new(DummyInput {}), Box::new(NullDisplay {}))
+            .registers([9; 16])
+            .build()
+            .unwrap();
+        // 6001: V0 = 1, A300: I = 300, F055: store V0, 1206: loop
+        chip8.load_rom_bytes(vec![0x60, 0x01, 0xA3, 0x00, 0xF0, 0x55, 0x12, 0x06]);
+        for _ in 0..4 {
+            chip8.step().unwrap();
+        }
+        assert_eq!(1, chip8.memory()[0x300]);
+
+        chip8.reset();
+        assert_eq!(0, chip8.memory()[0x300]);
+        assert_eq!(0x60, chip8.memory()[0x200]);
+        assert_eq!(0x200, chip8.pc());
+        assert_eq!(0, chip8.i());
+        assert_eq!(&[9; 16], chip8.registers());
+    }
+}
@@ -0,0 +1,160 @@
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::fs;
+
+use serde_json::Value;
+
+use crate::cpu::Quirks;
+
+/// What the database knows about a ROM.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RomInfo {
+    pub title: String,
+    pub authors: Vec<String>,
+    /// The first platform listed for the ROM, e.g. `originalChip8` or `superchip`.
+    pub platform: Option<String>,
+    pub quirks: Option<Quirks>,
+    pub instructions_per_frame: Option<u32>,
+    /// Colours as `#RRGGBB`, background first, then the foreground.
+    pub colours: Vec<String>,
+    /// Hex keys for named actions, e.g. `up` or `a`.
+    pub keys: Vec<(String, u8)>,
+}
+
+/// ROM metadata keyed by SHA-1, read from the `programs.json` file of the community
+/// chip-8-database (<https://github.com/chip-8/chip-8-database>).
+#[derive(Clone, Debug, Default)]
+pub struct RomDatabase {
+    roms: BTreeMap<String, RomInfo>,
+}
+
+impl RomDatabase {
+    #[cfg(feature = "std")]
+    pub fn load(path: &str) -> Result<RomDatabase, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("Cannot read ROM database {}: {}", path, e))?;
+        return RomDatabase::parse(&text);
+    }
+
+    pub fn parse(text: &str) -> Result<RomDatabase, String> {
+        let programs: Value = serde_json::from_str(text).map_err(|e| format!("Invalid ROM database: {}", e))?;
+        let programs = programs.as_array().ok_or("Invalid ROM database: expected a list of programs")?;
+
+        let mut roms = BTreeMap::new();
+        for program in programs {
+            let title = program["title"].as_str().unwrap_or("").to_string();
+            let authors: Vec<String> = program["authors"].as_array()
+                .map(|authors| authors.iter().filter_map(|author| author.as_str()).map(String::from).collect())
+                .unwrap_or_default();
+
+            let files = match program["roms"].as_object() {
+                Some(files) => files,
+                None => continue,
+            };
+            for (sha1, rom) in files {
+                roms.insert(sha1.to_lowercase(), RomInfo {
+                    title: title.clone(),
+                    authors: authors.clone(),
+                    platform: rom["platforms"][0].as_str().map(String::from),
+                    quirks: rom_quirks(rom),
+                    instructions_per_frame: rom["tickrate"].as_u64().map(|tickrate| tickrate as u32),
+                    colours: rom["colors"]["pixels"].as_array()
+                        .map(|colours| colours.iter().filter_map(|colour| colour.as_str()).map(String::from).collect())
+                        .unwrap_or_default(),
+                    keys: rom["keys"].as_object()
+                        .map(|keys| keys.iter().filter_map(|(name, key)| Some((name.clone(), key.as_u64()? as u8))).collect())
+                        .unwrap_or_default(),
+                });
+            }
+        }
+        return Ok(RomDatabase { roms });
+    }
+
+    pub fn lookup(&self, rom: &[u8]) -> Option<&RomInfo> {
+        return self.roms.get(&sha1(rom));
+    }
+}
+
+pub fn sha1(bytes: &[u8]) -> String {
+    return sha1_smol::Sha1::from(bytes).digest().to_string();
+}
+
+/// The quirks of the ROM's first platform, with the ROM's own `quirkyPlatforms` overrides.
+fn rom_quirks(rom: &Value) -> Option<Quirks> {
+    let platform = rom["platforms"][0].as_str()?;
+    let mut quirks = platform_quirks(platform)?;
+    if let Some(overrides) = rom["quirkyPlatforms"][platform].as_object() {
+        for (name, value) in overrides {
+            let enabled = value.as_bool().unwrap_or(false);
+            match name.as_str() {
+                "logic" => quirks.vf_reset = enabled,
+                "memoryLeaveIUnchanged" => quirks.memory = !enabled,
+                "memoryIncrementByX" => quirks.memory = enabled,
+                "vblank" => quirks.display_wait = enabled,
+                "wrap" => quirks.clipping = !enabled,
+                "shift" => quirks.shifting = enabled,
+                "jump" => quirks.jumping = enabled,
+                _ => {}
+            }
+        }
+    }
+    return Some(quirks);
+}
+
+/// Quirks of the platforms in the database's `platforms.json`.
+fn platform_quirks(platform: &str) -> Option<Quirks> {
+    return match platform {
+        "originalChip8" | "hybridVIP" => Some(Quirks::chip8()),
+        "modernChip8" => Some(Quirks { vf_reset: false, memory: true, display_wait: false, clipping: true, shifting: false, jumping: false }),
+        "chip48" => Some(Quirks { vf_reset: false, memory: true, display_wait: false, clipping: true, shifting: true, jumping: true }),
+        "superchip1" | "superchip" | "megachip8" => Some(Quirks::schip()),
+        "xochip" => Some(Quirks::xochip()),
+        _ => None,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cpu::database::{sha1, RomDatabase};
+    use crate::cpu::Quirks;
+
+    const PROGRAMS: &str = r##"[
+        {
+            "title": "Test",
+            "authors": ["Someone"],
+            "roms": {
+                "A9993E364706816ABA3E25717850C26C9CD0D89D": {
+                    "file": "test.ch8",
+                    "platforms": ["superchip", "xochip"],
+                    "quirkyPlatforms": { "superchip": { "shift": false } },
+                    "tickrate": 30,
+                    "colors": { "pixels": ["#101010", "#f0f0f0"] },
+                    "keys": { "up": 5, "a": 6 }
+                }
+            }
+        },
+        { "title": "No ROMs" }
+    ]"##;
+
+    #[test]
+    fn lookup_by_sha1() {
+        let database = RomDatabase::parse(PROGRAMS).unwrap();
+        let info = database.lookup(b"abc").unwrap();
+
+        assert_eq!("Test", info.title);
+        assert_eq!(vec![String::from("Someone")], info.authors);
+        assert_eq!(Some(String::from("superchip")), info.platform);
+        assert_eq!(Some(Quirks { shifting: false, ..Quirks::schip() }), info.quirks);
+        assert_eq!(Some(30), info.instructions_per_frame);
+        assert_eq!(vec!["#101010", "#f0f0f0"], info.colours);
+        assert!(info.keys.contains(&(String::from("up"), 5)));
+        assert!(database.lookup(b"abcd").is_none());
+    }
+
+    #[test]
+    fn sha1_hex() {
+        assert_eq!("a9993e364706816aba3e25717850c26c9cd0d89d", sha1(b"abc"));
+    }
+}
@@ -0,0 +1,176 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::cpu::{Chip8, Instruction};
+
+/// The registers, timers and stack at one point in time, without RAM and screen, so that
+/// it is cheap to take every frame or even every instruction.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snapshot {
+    pub pc: u16,
+    pub i: u16,
+    pub registers: [u8; 16],
+    pub stack: Vec<u16>,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    /// The instruction executed last.
+    pub current_instruction: Option<Instruction>,
+    /// The instruction at the PC, executed next.
+    pub next_instruction: Option<Instruction>,
+}
+
+/// Read-only views of the machine, for frontends and tools.
+impl Chip8 {
+    /// V0 to VF.
+    pub fn registers(&self) -> &[u8; 16] {
+        return &self.registers;
+    }
+
+    pub fn pc(&self) -> u16 {
+        return self.pc;
+    }
+
+    pub fn i(&self) -> u16 {
+        return self.i;
+    }
+
+    /// Return addresses, the innermost call last.
+    pub fn stack(&self) -> &[u16] {
+        return &self.stack;
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        return self.delay_timer;
+    }
+
+    pub fn sound_timer(&self) -> u8 {
+        return self.sound_timer;
+    }
+
+    /// All of RAM, including the font and the interpreter area.
+    pub fn memory(&self) -> &[u8] {
+        return &self.ram;
+    }
+
+    /// The screen as it is now, which may be ahead of what the display was last shown.
+    pub fn framebuffer(&self) -> &[[bool; 32]; 64] {
+        return &self.display;
+    }
+
+    /// Whether the program ended itself with 00FD.
+    pub fn has_exited(&self) -> bool {
+        return self.exited;
+    }
+
+    pub fn current_instruction(&self) -> Option<Instruction> {
+        return self.last_instruction;
+    }
+
+    /// Decodes the instruction at the PC without executing it; none when the PC is past the
+    /// end of memory.
+    pub fn next_instruction(&self) -> Option<Instruction> {
+        let pc = self.pc as usize;
+        return Some(Instruction::new(*self.ram.get(pc)?, *self.ram.get(pc + 1)?));
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        return Snapshot {
+            pc: self.pc,
+            i: self.i,
+            registers: self.registers,
+            stack: self.stack.clone(),
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+            current_instruction: self.last_instruction,
+            next_instruction: self.next_instruction(),
+        };
+    }
+
+    /// Write access for debuggers. A program never sees its state change like this, so
+    /// anything else should go through the ROM, the input or the builder.
+    pub fn debugger(&mut self) -> Debugger<'_> {
+        return Debugger { chip8: self };
+    }
+}
+
+/// Changes the machine behind the running program's back. Only meant for debuggers.
+pub struct Debugger<'a> {
+    chip8: &'a mut Chip8,
+}
+
+impl Debugger<'_> {
+    /// Writes straight into RAM, wrapping the address into memory, e.g. to pass options to a
+    /// ROM that reads them from memory. Protection does not apply.
+    pub fn poke(&mut self, address: u16, value: u8) {
+        self.chip8.poke(address, value);
+    }
+
+    pub fn set_register(&mut self, register: u8, value: u8) -> Result<(), String> {
+        if register > 0xF {
+            return Err(format!("There is no register V{:X}", register));
+        }
+        self.chip8.registers[register as usize] = value;
+        Ok(())
+    }
+
+    pub fn set_pc(&mut self, pc: u16) -> Result<(), String> {
+        if pc as usize + 1 >= self.chip8.ram.len() {
+            return Err(format!("PC {:#05x} is outside the {} bytes of memory", pc, self.chip8.ram.len()));
+        }
+        self.chip8.pc = pc;
+        Ok(())
+    }
+
+    pub fn set_i(&mut self, i: u16) {
+        self.chip8.i = i;
+    }
+
+    pub fn set_delay_timer(&mut self, value: u8) {
+        self.chip8.delay_timer = value;
+    }
+
+    pub fn set_sound_timer(&mut self, value: u8) {
+        self.chip8.sound_timer = value;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::basic::{DummyInput, NullDisplay};
+    use crate::cpu::{Chip8, Instruction};
+
+    #[test]
+    fn snapshot_follows_execution() {
+        let mut chip8 = Chip8::new(Box::new(DummyInput {}), Box::new(NullDisplay {}));
+        // 6A07: VA = 7, 2206: call 206, 1204: loop, 00EE: return
+        chip8.load_rom_bytes(vec![0x6A, 0x07, 0x22, 0x06, 0x12, 0x04, 0x00, 0xEE]);
+        assert_eq!(None, chip8.current_instruction());
+        assert_eq!(Some(Instruction::from_opcode(0x6A07)), chip8.next_instruction());
+
+        chip8.step().unwrap();
+        chip8.step().unwrap();
+        let snapshot = chip8.snapshot();
+        assert_eq!(0x206, snapshot.pc);
+        assert_eq!(7, snapshot.registers[0xA]);
+        assert_eq!(vec![0x204], snapshot.stack);
+        assert_eq!(Some(Instruction::from_opcode(0x2206)), snapshot.current_instruction);
+        assert_eq!(Some(Instruction::from_opcode(0x00EE)), snapshot.next_instruction);
+        assert_eq!(0x6A, chip8.memory()[0x200]);
+    }
+
+    #[test]
+    fn debugger_writes_are_checked() {
+        let mut chip8 = Chip8::new(Box::new(DummyInput {}), Box::new(NullDisplay {}));
+        let mut debugger = chip8.debugger();
+        debugger.set_register(0xF, 1).unwrap();
+        assert!(debugger.set_register(0x10, 1).is_err());
+        debugger.set_pc(0x300).unwrap();
+        assert!(debugger.set_pc(0xFFF).is_err());
+        debugger.poke(0x300, 0xAB);
+
+        assert_eq!(1, chip8.registers()[0xF]);
+        assert_eq!(0x300, chip8.pc());
+        assert_eq!(0xAB, chip8.memory()[0x300]);
+    }
+}
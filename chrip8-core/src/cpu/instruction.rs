@@ -1,6 +1,9 @@
-use std::fmt;
+use alloc::fmt;
+use alloc::string::String;
 
-pub(crate) struct Instruction {
+/// A decoded two-byte opcode, split into its four nibbles, e.g. `D` `X` `Y` `N` for DXYN.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Instruction {
     first_byte: u8,
     second_byte: u8,
     pub first_nibble: u8,
@@ -21,14 +24,25 @@ impl Instruction {
         };
     }
 
+    pub fn from_opcode(opcode: u16) -> Instruction {
+        return Instruction::new((opcode >> 8) as u8, opcode as u8);
+    }
+
+    pub fn opcode(&self) -> u16 {
+        return ((self.first_byte as u16) << 8) | self.second_byte as u16;
+    }
+
+    /// The lowest three nibbles, the NNN address of e.g. 1NNN.
     pub fn byte_sum_3(&self) -> u16 {
         return ((self.second_nibble as u16) << 8) + ((self.third_nibble as u16) << 4) + (self.fourth_nibble as u16);
     }
 
+    /// The lowest byte, the NN of e.g. 6XNN.
     pub fn byte_sum_2(&self) -> u8 {
         return (self.third_nibble << 4) + self.fourth_nibble;
     }
 
+    #[allow(clippy::inherent_to_string)]
     pub fn to_string(&self) -> String {
         return fmt::format(format_args!("{:#04x} {:#04x}", self.first_byte, self.second_byte));
     }
@@ -55,4 +69,12 @@ mod tests {
         let i : Instruction = Instruction::new(0x12, 0x28);
         assert_eq!(0x228, i.byte_sum_3());
     }
+
+    #[test]
+    fn opcode_round_trip() {
+        let instruction = Instruction::from_opcode(0xD12F);
+        assert_eq!(0xD, instruction.first_nibble);
+        assert_eq!(0xF, instruction.fourth_nibble);
+        assert_eq!(0xD12F, instruction.opcode());
+    }
 }
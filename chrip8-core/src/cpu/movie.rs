@@ -0,0 +1,255 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::fs::{self, File};
+#[cfg(feature = "std")]
+use std::io::{self, BufWriter, Write};
+
+use crate::cpu::{Chip8State, Quirks, RandomAlgorithm};
+use crate::cpu::rewind::state_to_bytes;
+
+const MOVIE_HEADER: &str = "chip8-movie 1";
+
+/// 64-bit FNV-1a, used to fingerprint ROMs and machine states.
+pub fn hash(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    return hash;
+}
+
+impl Chip8State {
+    pub fn hash(&self) -> u64 {
+        return hash(&state_to_bytes(self));
+    }
+}
+
+/// One recorded frame: the keypad bitmask the machine saw and the state hash it ended with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MovieFrame {
+    pub keypad: u16,
+    pub state_hash: u64,
+}
+
+/// Everything needed to reproduce a run: the ROM, the quirks, the RNG seed, the speed, the
+/// memory layout and the inputs.
+///
+/// On disk a movie is a small text file:
+///
+/// ```text
+/// chip8-movie 1
+/// rom 9e3a1c0d5b2f7a64
+/// quirks clipping,shifting
+/// rng xorshift
+/// seed 00000000000004d2
+/// ipf 8
+/// memory 1000
+/// font 032
+/// start 200
+/// registers 00000000000000000000000000000000
+/// 0000 5f0c2a9be1d37a40
+/// 0020 0b7a3c4e9d12f805
+/// ```
+///
+/// with one `keypad state_hash` line per frame. Memory size, font address, program start and
+/// the registers at power on are in hex.
+#[derive(Debug, PartialEq)]
+pub struct Movie {
+    pub rom_hash: u64,
+    pub quirks: Quirks,
+    pub random_algorithm: RandomAlgorithm,
+    pub seed: u64,
+    pub instructions_per_frame: u32,
+    pub memory_size: usize,
+    pub font_address: u16,
+    pub program_start: u16,
+    pub registers: [u8; 16],
+    pub frames: Vec<MovieFrame>,
+}
+
+impl Movie {
+    #[cfg(feature = "std")]
+    pub fn load(path: String) -> io::Result<Movie> {
+        let text = fs::read_to_string(path)?;
+        return Movie::parse(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e));
+    }
+
+    pub fn parse(text: &str) -> Result<Movie, String> {
+        let mut lines = text.lines();
+        if lines.next() != Some(MOVIE_HEADER) {
+            return Err(String::from("not a chip8 movie"));
+        }
+
+        let rom_hash = parse_field(lines.next(), "rom")?;
+        let quirks = field(lines.next(), "quirks")?.parse()?;
+        let random_algorithm = field(lines.next(), "rng")?.parse()?;
+        let seed = parse_field(lines.next(), "seed")?;
+        let instructions_per_frame = field(lines.next(), "ipf")?.parse().map_err(|_| "malformed ipf")?;
+        let memory_size = parse_field(lines.next(), "memory")? as usize;
+        let font_address = parse_field(lines.next(), "font")? as u16;
+        let program_start = parse_field(lines.next(), "start")? as u16;
+        let registers = parse_registers(field(lines.next(), "registers")?)?;
+
+        let mut frames = Vec::new();
+        for line in lines {
+            let (keypad, state_hash) = line.split_once(' ').ok_or("malformed frame")?;
+            frames.push(MovieFrame {
+                keypad: u16::from_str_radix(keypad, 16).map_err(|e| e.to_string())?,
+                state_hash: u64::from_str_radix(state_hash, 16).map_err(|e| e.to_string())?,
+            });
+        }
+
+        return Ok(Movie {
+            rom_hash, quirks, random_algorithm, seed, instructions_per_frame, memory_size, font_address, program_start, registers, frames,
+        });
+    }
+
+    /// The header lines, everything but the frames.
+    #[cfg(any(feature = "std", test))]
+    pub fn header(&self) -> String {
+        let registers: String = self.registers.iter().map(|register| format!("{:02x}", register)).collect();
+        return format!(
+            "{}\nrom {:016x}\nquirks {}\nrng {}\nseed {:016x}\nipf {}\nmemory {:x}\nfont {:03x}\nstart {:03x}\nregisters {}\n",
+            MOVIE_HEADER, self.rom_hash, self.quirks, self.random_algorithm, self.seed, self.instructions_per_frame,
+            self.memory_size, self.font_address, self.program_start, registers,
+        );
+    }
+}
+
+fn parse_registers(value: &str) -> Result<[u8; 16], String> {
+    if value.len() != 32 || !value.is_ascii() {
+        return Err(String::from("expected 16 registers"));
+    }
+    let mut registers = [0; 16];
+    for (i, register) in registers.iter_mut().enumerate() {
+        *register = u8::from_str_radix(&value[i * 2..i * 2 + 2], 16).map_err(|e| e.to_string())?;
+    }
+    return Ok(registers);
+}
+
+fn field<'a>(line: Option<&'a str>, name: &str) -> Result<&'a str, String> {
+    let line = line.ok_or("truncated header")?;
+    return match line.split_once(' ') {
+        Some((key, value)) if key == name => Ok(value),
+        _ => Err(format!("expected {}", name)),
+    };
+}
+
+fn parse_field(line: Option<&str>, name: &str) -> Result<u64, String> {
+    return u64::from_str_radix(field(line, name)?, 16).map_err(|e| e.to_string());
+}
+
+/// Appends frames to a movie file as they are played, so that nothing is lost if the
+/// emulator is closed abruptly.
+#[cfg(feature = "std")]
+pub struct MovieRecorder {
+    writer: BufWriter<File>,
+}
+
+#[cfg(feature = "std")]
+impl MovieRecorder {
+    /// Writes the header of `movie`, whose frames are left out.
+    pub fn create(path: String, movie: &Movie) -> io::Result<MovieRecorder> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        write!(writer, "{}", movie.header())?;
+        writer.flush()?;
+        return Ok(MovieRecorder { writer });
+    }
+
+    pub fn record(&mut self, keypad: u16, state_hash: u64) -> io::Result<()> {
+        writeln!(self.writer, "{:04x} {:016x}", keypad, state_hash)?;
+        return self.writer.flush();
+    }
+}
+
+/// Feeds the recorded keypad states back frame by frame and checks that every frame
+/// ends in the same state it did while recording.
+pub struct MoviePlayer {
+    movie: Movie,
+    frame: usize,
+    diverged_at: Option<usize>,
+}
+
+impl MoviePlayer {
+    pub fn new(movie: Movie) -> MoviePlayer {
+        return MoviePlayer {
+            movie,
+            frame: 0,
+            diverged_at: None,
+        };
+    }
+
+    pub fn frame(&self) -> usize {
+        return self.frame;
+    }
+
+    pub fn next_keypad(&mut self) -> Option<u16> {
+        return self.movie.frames.get(self.frame).map(|frame| frame.keypad);
+    }
+
+    /// Compares the state hash at the end of the current frame with the recorded one.
+    /// Returns the frame number the first time the two differ.
+    pub fn verify(&mut self, state_hash: u64) -> Option<usize> {
+        let frame = self.frame;
+        self.frame += 1;
+
+        let expected = self.movie.frames.get(frame)?.state_hash;
+        if expected != state_hash && self.diverged_at.is_none() {
+            self.diverged_at = Some(frame);
+            return self.diverged_at;
+        }
+        return None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cpu::{Quirks, RandomAlgorithm};
+    use crate::cpu::movie::{parse_registers, Movie, MovieFrame, MoviePlayer};
+
+    #[test]
+    fn parse_movie() {
+        let header = "chip8-movie 1\nrom 00000000000000ff\nquirks schip\nrng vip\nseed 000000000000002a\nipf 15\nmemory 1000\nfont 050\nstart 200\nregisters 0102000000000000000000000000000f\n";
+        let movie = Movie::parse(&format!("{}0010 0000000000000001\n0000 0000000000000002\n", header)).unwrap();
+
+        assert_eq!(Movie {
+            rom_hash: 0xFF,
+            quirks: Quirks::schip(),
+            random_algorithm: RandomAlgorithm::CosmacVip,
+            seed: 42,
+            instructions_per_frame: 15,
+            memory_size: 4096,
+            font_address: 0x50,
+            program_start: 0x200,
+            registers: [1, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xF],
+            frames: vec![
+                MovieFrame { keypad: 0x10, state_hash: 1 },
+                MovieFrame { keypad: 0x00, state_hash: 2 },
+            ],
+        }, movie);
+        assert_eq!(Ok(&movie), Movie::parse(&movie.header()).map(|parsed| Movie { frames: movie.frames.clone(), ..parsed }).as_ref());
+    }
+
+    #[test]
+    fn non_ascii_registers_are_an_error() {
+        // 32 bytes, but the two-byte character would be split in half
+        assert!(parse_registers("é000000000000000000000000000000").is_err());
+    }
+
+    #[test]
+    fn reports_first_divergent_frame_once() {
+        let frames = (0..4).map(|i| MovieFrame { keypad: 0, state_hash: i }).collect();
+        let mut player = MoviePlayer::new(Movie {
+            rom_hash: 0, quirks: Quirks::default(), random_algorithm: RandomAlgorithm::Xorshift, seed: 0,
+            instructions_per_frame: 8, memory_size: 4096, font_address: 50, program_start: 0x200, registers: [0; 16], frames,
+        });
+
+        assert_eq!(None, player.verify(0));
+        assert_eq!(Some(1), player.verify(7));
+        assert_eq!(None, player.verify(8));
+        assert_eq!(None, player.verify(3));
+    }
+}
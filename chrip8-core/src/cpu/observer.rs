@@ -0,0 +1,177 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use crate::cpu::{Instruction, StopReason};
+
+/// Which of the two 60 Hz timers started or stopped.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Timer {
+    Delay,
+    /// The buzzer sounds while this one runs.
+    Sound,
+}
+
+/// Watches the machine run, e.g. to profile, trace or script a ROM, without touching the
+/// core. Every method does nothing unless overridden. Observers run on the machine's
+/// thread, in the middle of an instruction, so they should be quick; state they collect
+/// can be shared with the outside through an `Arc`, as with `Display`.
+pub trait Observer : Send {
+    /// Called with the address of the instruction, before it runs.
+    fn before_instruction(&mut self, _pc: u16, _instruction: Instruction) {}
+
+    /// Called with the address of the instruction, after it ran without error.
+    fn after_instruction(&mut self, _pc: u16, _instruction: Instruction) {}
+
+    /// Called right after 00E0 or DXYN, not only once per frame like `Display::draw`.
+    fn screen_changed(&mut self, _framebuffer: &[[bool; 32]; 64]) {}
+
+    fn memory_written(&mut self, _address: u16, _old: u8, _new: u8) {}
+
+    /// Called when a timer is set from zero to a count, or counts down to zero.
+    fn timer_changed(&mut self, _timer: Timer, _running: bool) {}
+
+    /// Called by 2NNN with the return address and the stack depth after the push.
+    fn stack_pushed(&mut self, _address: u16, _depth: usize) {}
+
+    /// Called by 00EE with the return address and the stack depth after the pop.
+    fn stack_popped(&mut self, _address: u16, _depth: usize) {}
+
+    /// Called when the machine stops: on an error, when the program exits with 00FD, and when
+    /// `Control::stop` ends `execute`.
+    fn halted(&mut self, _reason: &StopReason) {}
+}
+
+/// Identifies an observer, to remove it again.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ObserverId(usize);
+
+#[derive(Default)]
+pub(super) struct Observers {
+    next_id: usize,
+    observers: Vec<(ObserverId, Box<dyn Observer>)>,
+}
+
+impl Observers {
+    pub fn add(&mut self, observer: Box<dyn Observer>) -> ObserverId {
+        let id = ObserverId(self.next_id);
+        self.next_id += 1;
+        self.observers.push((id, observer));
+        return id;
+    }
+
+    pub fn remove(&mut self, id: ObserverId) -> Option<Box<dyn Observer>> {
+        let index = self.observers.iter().position(|(observer_id, _)| *observer_id == id)?;
+        return Some(self.observers.remove(index).1);
+    }
+
+    pub fn each(&mut self, mut notify: impl FnMut(&mut dyn Observer)) {
+        for (_, observer) in self.observers.iter_mut() {
+            notify(observer.as_mut());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::basic::{DummyInput, NullDisplay};
+    use crate::cpu::{Chip8, Instruction, Observer, StopReason, Timer};
+
+    /// Writes down everything but the instructions.
+    struct Recorder {
+        events: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Observer for Recorder {
+        fn screen_changed(&mut self, _framebuffer: &[[bool; 32]; 64]) {
+            self.events.lock().unwrap().push(String::from("screen"));
+        }
+
+        fn memory_written(&mut self, address: u16, old: u8, new: u8) {
+            self.events.lock().unwrap().push(format!("write {:03x} {} {}", address, old, new));
+        }
+
+        fn timer_changed(&mut self, timer: Timer, running: bool) {
+            self.events.lock().unwrap().push(format!("{:?} {}", timer, running));
+        }
+
+        fn stack_pushed(&mut self, address: u16, depth: usize) {
+            self.events.lock().unwrap().push(format!("push {:03x} {}", address, depth));
+        }
+
+        fn stack_popped(&mut self, address: u16, depth: usize) {
+            self.events.lock().unwrap().push(format!("pop {:03x} {}", address, depth));
+        }
+
+        fn halted(&mut self, reason: &StopReason) {
+            self.events.lock().unwrap().push(format!("halt {}", reason));
+        }
+    }
+
+    /// Counts the instructions run at each address.
+    struct Profiler {
+        counts: Arc<Mutex<Vec<(u16, u16)>>>,
+    }
+
+    impl Observer for Profiler {
+        fn after_instruction(&mut self, pc: u16, instruction: Instruction) {
+            self.counts.lock().unwrap().push((pc, instruction.opcode()));
+        }
+    }
+
+    #[test]
+    fn observers_see_what_the_machine_does() {
+        let mut chip8 = Chip8::new(Box::new(DummyInput {}), Box::new(NullDisplay {}));
+        let events = Arc::new(Mutex::new(Vec::new()));
+        chip8.add_observer(Box::new(Recorder { events: events.clone() }));
+        // 6081: V0 = 129, A300: I = 300, F033: BCD of V0, 220A: call 20A, 00EE: return
+        // (never reached), 20A: 00E0: clear, F018: sound timer = V0, 00EE: return, 00EE again
+        chip8.load_rom_bytes(vec![0x60, 0x81, 0xA3, 0x00, 0xF0, 0x33, 0x22, 0x0A, 0x00, 0xEE, 0x00, 0xE0, 0xF0, 0x18, 0x00, 0xEE]);
+        for _ in 0..7 {
+            chip8.step().unwrap();
+        }
+        assert!(chip8.step().is_err());
+
+        assert_eq!(vec![
+            "write 300 0 1", "write 301 0 2", "write 302 0 9",
+            "push 208 1", "screen", "Sound true", "pop 208 0",
+            "halt Return from a subroutine with an empty stack",
+        ], *events.lock().unwrap());
+
+        events.lock().unwrap().clear();
+        // 00FD: exit
+        chip8.load_rom_bytes(vec![0x00, 0xFD]);
+        chip8.step().unwrap();
+        chip8.step().unwrap();
+        assert_eq!(vec!["halt the program exited"], *events.lock().unwrap());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn observers_hear_the_machine_quit() {
+        let mut chip8 = Chip8::new(Box::new(DummyInput {}), Box::new(NullDisplay {}));
+        let events = Arc::new(Mutex::new(Vec::new()));
+        chip8.add_observer(Box::new(Recorder { events: events.clone() }));
+        // 1200: jump to itself
+        chip8.load_rom_bytes(vec![0x12, 0x00]);
+        chip8.control().stop();
+        assert_eq!(StopReason::Quit, chip8.execute());
+        assert_eq!(vec!["halt quit"], *events.lock().unwrap());
+    }
+
+    #[test]
+    fn removed_observers_hear_nothing_more() {
+        let mut chip8 = Chip8::new(Box::new(DummyInput {}), Box::new(NullDisplay {}));
+        let counts = Arc::new(Mutex::new(Vec::new()));
+        let id = chip8.add_observer(Box::new(Profiler { counts: counts.clone() }));
+        // 1200: jump to itself
+        chip8.load_rom_bytes(vec![0x12, 0x00]);
+
+        chip8.step().unwrap();
+        assert!(chip8.remove_observer(id).is_some());
+        chip8.step().unwrap();
+        assert_eq!(vec![(0x200, 0x1200)], *counts.lock().unwrap());
+        assert!(chip8.remove_observer(id).is_none());
+    }
+}
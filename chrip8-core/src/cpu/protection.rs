@@ -0,0 +1,170 @@
+use alloc::format;
+use alloc::string::String;
+use core::str::FromStr;
+use log::warn;
+
+use crate::cpu::Chip8;
+
+/// What happens when a program writes to a protected region.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ProtectionAction {
+    /// Logs a warning and lets the write through.
+    Warn,
+    /// Lets the write through, ends the frame and pauses `execute`, so that the machine can be
+    /// looked at right after the write. Hosts calling `run_frame` ask `take_trap`.
+    Trap,
+    /// Stops the machine with an error before the write happens.
+    Error,
+}
+
+impl FromStr for ProtectionAction {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<ProtectionAction, String> {
+        return match name {
+            "warn" => Ok(ProtectionAction::Warn),
+            "trap" => Ok(ProtectionAction::Trap),
+            "error" => Ok(ProtectionAction::Error),
+            _ => Err(format!("Unknown protection action: {}, expected warn, trap or error", name)),
+        };
+    }
+}
+
+/// Regions of memory that programs should only read, to catch FX33 and FX55 stomping over
+/// them. Writes through the debugger are not checked.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Protection {
+    /// Everything below the program start except the font.
+    pub interpreter: bool,
+    pub font: bool,
+    /// The bytes of the ROM loaded last; some programs modify their own code on purpose.
+    pub program: bool,
+    pub action: ProtectionAction,
+}
+
+/// Parses `all` or a comma separated list of regions: interpreter, font and program. The
+/// action is `Warn`.
+impl FromStr for Protection {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Protection, String> {
+        let mut protection = Protection { interpreter: false, font: false, program: false, action: ProtectionAction::Warn };
+        for name in value.split(',') {
+            match name.trim() {
+                "all" => {
+                    protection.interpreter = true;
+                    protection.font = true;
+                    protection.program = true;
+                }
+                "interpreter" => protection.interpreter = true,
+                "font" => protection.font = true,
+                "program" => protection.program = true,
+                name => return Err(format!("Unknown memory region: {}, expected interpreter, font, program or all", name)),
+            }
+        }
+        return Ok(protection);
+    }
+}
+
+impl Chip8 {
+    pub fn set_protection(&mut self, protection: Option<Protection>) {
+        self.options.protection = protection;
+    }
+
+    /// The reason the last trap fired, once; `run_frame` ends the frame early on a trap.
+    pub fn take_trap(&mut self) -> Option<String> {
+        return self.trap.take();
+    }
+
+    /// The protected region `address` lies in, if any.
+    fn protected_region(&self, address: usize) -> Option<&'static str> {
+        let protection = self.options.protection?;
+        let font_start = self.options.font_address as usize;
+        let program_start = self.options.program_start as usize;
+        if (font_start..font_start + self.options.font.len()).contains(&address) {
+            return if protection.font { Some("font") } else { None };
+        }
+        if address < program_start && protection.interpreter {
+            return Some("interpreter area");
+        }
+        if (program_start..program_start + self.rom.len()).contains(&address) && protection.program {
+            return Some("program");
+        }
+        return None;
+    }
+
+    /// Called before an instruction writes `value` to `address`.
+    pub(super) fn check_write(&mut self, address: usize, value: u8) -> Result<(), String> {
+        let region = match self.protected_region(address) {
+            Some(region) => region,
+            None => return Ok(()),
+        };
+        let opcode = self.last_instruction.map_or(0, |instruction| instruction.opcode());
+        let message = format!("{:04X} at {:#05x} wrote {:#04x} to {:#05x} in the {}", opcode, self.instruction_address, value, address, region);
+
+        match self.options.protection.map(|protection| protection.action) {
+            Some(ProtectionAction::Error) => return Err(message),
+            Some(ProtectionAction::Trap) => {
+                self.trap.get_or_insert(message);
+            }
+            _ => warn!("{}", message),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::basic::{DummyInput, NullDisplay};
+    use crate::cpu::{Chip8, Protection, ProtectionAction};
+
+    /// 6107: V1 = 7, A0xx: I = xx, F133: BCD of V1 at I, 1206: loop
+    fn stomping_rom(address: u8) -> Vec<u8> {
+        return vec![0x61, 0x07, 0xA0, address, 0xF1, 0x33, 0x12, 0x06];
+    }
+
+    fn machine(regions: &str, action: ProtectionAction) -> Chip8 {
+        let mut chip8 = Chip8::new(Box::new(DummyInput {}), Box::new(NullDisplay {}));
+        let mut protection: Protection = regions.parse().unwrap();
+        protection.action = action;
+        chip8.set_protection(Some(protection));
+        return chip8;
+    }
+
+    #[test]
+    fn writes_to_the_font_are_caught() {
+        let mut chip8 = machine("font", ProtectionAction::Error);
+        // the font starts at 50 = 0x32
+        chip8.load_rom_bytes(stomping_rom(0x32));
+        chip8.step().unwrap();
+        chip8.step().unwrap();
+        assert_eq!(Err(String::from("F133 at 0x204 wrote 0x00 to 0x032 in the font")), chip8.step());
+        assert_eq!(0xF0, chip8.memory()[0x32]);
+
+        // only the font is protected
+        let mut chip8 = machine("font", ProtectionAction::Error);
+        chip8.load_rom_bytes(stomping_rom(0x10));
+        for _ in 0..4 {
+            chip8.step().unwrap();
+        }
+    }
+
+    #[test]
+    fn traps_end_the_frame_after_the_write() {
+        let mut chip8 = machine("interpreter,program", ProtectionAction::Trap);
+        chip8.load_rom_bytes(stomping_rom(0x10));
+        chip8.run_frame().unwrap();
+        assert_eq!(7, chip8.memory()[0x12]);
+        assert_eq!(0x206, chip8.pc());
+        assert!(chip8.take_trap().unwrap().contains("interpreter area"));
+        assert_eq!(None, chip8.take_trap());
+    }
+
+    #[test]
+    fn region_names() {
+        let all: Protection = "all".parse().unwrap();
+        assert!(all.interpreter && all.font && all.program);
+        assert!("stack".parse::<Protection>().is_err());
+        assert_eq!(Ok(ProtectionAction::Trap), "trap".parse());
+    }
+}
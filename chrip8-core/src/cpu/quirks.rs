@@ -0,0 +1,129 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+
+/// Behaviours that differ between CHIP-8 interpreters, named as in Timendus' quirks test.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quirks {
+    /// 8XY1, 8XY2 and 8XY3 reset VF to 0.
+    pub vf_reset: bool,
+    /// FX55 and FX65 leave I pointing past the last register stored or loaded.
+    pub memory: bool,
+    /// DXYN waits for the next frame, so at most one sprite is drawn per frame.
+    pub display_wait: bool,
+    /// Sprites are cut off at the screen edges instead of wrapping around.
+    pub clipping: bool,
+    /// 8XY6 and 8XYE shift VX in place instead of shifting VY into VX.
+    pub shifting: bool,
+    /// BXNN jumps to XNN + VX instead of BNNN jumping to NNN + V0.
+    pub jumping: bool,
+}
+
+const NAMES: [&str; 6] = ["vf_reset", "memory", "display_wait", "clipping", "shifting", "jumping"];
+
+impl Quirks {
+    /// The original COSMAC VIP interpreter.
+    pub fn chip8() -> Quirks {
+        return Quirks { vf_reset: true, memory: true, display_wait: true, clipping: true, shifting: false, jumping: false };
+    }
+
+    /// SUPER-CHIP 1.1 as most modern games expect it.
+    pub fn schip() -> Quirks {
+        return Quirks { vf_reset: false, memory: false, display_wait: false, clipping: true, shifting: true, jumping: true };
+    }
+
+    pub fn xochip() -> Quirks {
+        return Quirks { vf_reset: false, memory: true, display_wait: false, clipping: false, shifting: false, jumping: false };
+    }
+
+    pub fn preset(name: &str) -> Option<Quirks> {
+        return match name {
+            "default" => Some(Quirks::default()),
+            "chip8" => Some(Quirks::chip8()),
+            "schip" => Some(Quirks::schip()),
+            "xochip" => Some(Quirks::xochip()),
+            _ => None,
+        };
+    }
+
+    fn flags(&self) -> [bool; 6] {
+        return [self.vf_reset, self.memory, self.display_wait, self.clipping, self.shifting, self.jumping];
+    }
+
+    /// Turns a single quirk on or off by name.
+    pub fn set(&mut self, name: &str, enabled: bool) -> Result<(), String> {
+        let quirk = match name {
+            "vf_reset" => &mut self.vf_reset,
+            "memory" => &mut self.memory,
+            "display_wait" => &mut self.display_wait,
+            "clipping" => &mut self.clipping,
+            "shifting" => &mut self.shifting,
+            "jumping" => &mut self.jumping,
+            _ => return Err(format!("Unknown quirk: {}", name)),
+        };
+        *quirk = enabled;
+        Ok(())
+    }
+}
+
+/// What this emulator has always done: clip sprites and shift VX in place.
+impl Default for Quirks {
+    fn default() -> Quirks {
+        return Quirks { vf_reset: false, memory: false, display_wait: false, clipping: true, shifting: true, jumping: false };
+    }
+}
+
+/// Lists the enabled quirks, e.g. `clipping,shifting`, or `none`.
+impl fmt::Display for Quirks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let enabled: Vec<&str> = NAMES.iter()
+            .zip(self.flags())
+            .filter(|(_, enabled)| *enabled)
+            .map(|(name, _)| *name)
+            .collect();
+        if enabled.is_empty() {
+            return write!(f, "none");
+        }
+        return write!(f, "{}", enabled.join(","));
+    }
+}
+
+/// Parses a preset name or a list of enabled quirks, as written by `Display`.
+impl FromStr for Quirks {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Quirks, String> {
+        if let Some(quirks) = Quirks::preset(value) {
+            return Ok(quirks);
+        }
+
+        let mut quirks = Quirks { vf_reset: false, memory: false, display_wait: false, clipping: false, shifting: false, jumping: false };
+        if value != "none" {
+            for name in value.split(',') {
+                quirks.set(name.trim(), true)?;
+            }
+        }
+        return Ok(quirks);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cpu::quirks::Quirks;
+
+    #[test]
+    fn display_round_trip() {
+        for quirks in [Quirks::default(), Quirks::chip8(), Quirks::schip(), Quirks::xochip()] {
+            assert_eq!(Ok(quirks), quirks.to_string().parse());
+        }
+        assert_eq!("none", "none".parse::<Quirks>().unwrap().to_string());
+    }
+
+    #[test]
+    fn presets_by_name() {
+        assert_eq!(Ok(Quirks::schip()), "schip".parse());
+        assert!("vip".parse::<Quirks>().is_err());
+    }
+}
@@ -0,0 +1,118 @@
+use alloc::format;
+use alloc::string::String;
+use core::fmt;
+use core::str::FromStr;
+
+/// The algorithm behind CXNN.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RandomAlgorithm {
+    /// 64-bit xorshift*, the default: cheap and statistically sound.
+    Xorshift,
+    /// Modelled on the COSMAC VIP interpreter: a pointer walks through the interpreter's
+    /// page (0x100-0x1FF), and the byte found there is added to the previous result,
+    /// which is then rotated. Results depend on what the ROM leaves in that page.
+    CosmacVip,
+}
+
+impl FromStr for RandomAlgorithm {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<RandomAlgorithm, String> {
+        return match name {
+            "xorshift" => Ok(RandomAlgorithm::Xorshift),
+            "vip" => Ok(RandomAlgorithm::CosmacVip),
+            _ => Err(format!("Unknown random algorithm: {}", name)),
+        };
+    }
+}
+
+impl fmt::Display for RandomAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            RandomAlgorithm::Xorshift => write!(f, "xorshift"),
+            RandomAlgorithm::CosmacVip => write!(f, "vip"),
+        };
+    }
+}
+
+/// Random number generator state, saved and restored together with the rest of the machine.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Random {
+    pub algorithm: RandomAlgorithm,
+    pub seed: u64,
+    pub state: u64,
+}
+
+impl Random {
+    pub fn new(algorithm: RandomAlgorithm, seed: u64) -> Random {
+        let state = match algorithm {
+            // xorshift never leaves the all-zero state
+            RandomAlgorithm::Xorshift => if seed == 0 { 0x9E3779B97F4A7C15 } else { seed },
+            RandomAlgorithm::CosmacVip => seed & 0xFFFF,
+        };
+        return Random { algorithm, seed, state };
+    }
+
+    pub fn next_byte(&mut self, ram: &[u8]) -> u8 {
+        return match self.algorithm {
+            RandomAlgorithm::Xorshift => self.xorshift(),
+            RandomAlgorithm::CosmacVip => self.cosmac_vip(ram),
+        };
+    }
+
+    fn xorshift(&mut self) -> u8 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        return (self.state.wrapping_mul(0x2545F4914F6CDD1D) >> 56) as u8;
+    }
+
+    fn cosmac_vip(&mut self, ram: &[u8]) -> u8 {
+        // low byte: the pointer into the interpreter page, high byte: the previous result
+        let pointer = (self.state as u8).wrapping_add(1);
+        let previous = (self.state >> 8) as u8;
+        let byte = ram.get(0x100 | pointer as usize).copied().unwrap_or(0);
+
+        let sum = byte as u16 + previous as u16 + pointer as u16;
+        let carry = (sum > 0xFF) as u8;
+        let result = ((sum as u8) >> 1) | (carry << 7);
+
+        self.state = ((result as u64) << 8) | pointer as u64;
+        return result;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cpu::random::{Random, RandomAlgorithm};
+
+    #[test]
+    fn same_seed_same_sequence() {
+        let ram = [0u8; 4096];
+        for algorithm in [RandomAlgorithm::Xorshift, RandomAlgorithm::CosmacVip] {
+            let mut a = Random::new(algorithm, 42);
+            let mut b = Random::new(algorithm, 42);
+            let a: Vec<u8> = (0..32).map(|_| a.next_byte(&ram)).collect();
+            let b: Vec<u8> = (0..32).map(|_| b.next_byte(&ram)).collect();
+            assert_eq!(a, b);
+        }
+    }
+
+    #[test]
+    fn vip_reads_the_interpreter_page() {
+        let mut ram = [0u8; 4096];
+        let mut empty = Random::new(RandomAlgorithm::CosmacVip, 0);
+        let first = empty.next_byte(&ram);
+
+        ram[0x101] = 0x80;
+        let mut filled = Random::new(RandomAlgorithm::CosmacVip, 0);
+        assert_ne!(first, filled.next_byte(&ram));
+    }
+
+    #[test]
+    fn xorshift_does_not_stall_on_zero_seed() {
+        let mut random = Random::new(RandomAlgorithm::Xorshift, 0);
+        let values: Vec<u8> = (0..8).map(|_| random.next_byte(&[])).collect();
+        assert!(values.iter().any(|value| *value != values[0]));
+    }
+}
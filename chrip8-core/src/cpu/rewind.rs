@@ -0,0 +1,279 @@
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+use crate::cpu::{Chip8State, Random, RandomAlgorithm};
+
+/// Ring buffer of per-frame machine states used to run a game backwards.
+///
+/// Only the most recent state is kept in full. Every older frame is stored as the
+/// XOR difference against the frame that followed it, run-length encoded so that
+/// the (mostly zero) unchanged bytes take almost no room.
+pub struct RewindBuffer {
+    capacity: usize,
+    /// Size of the machine's RAM, which the states do not record.
+    ram_len: usize,
+    latest: Option<Vec<u8>>,
+    deltas: VecDeque<Vec<u8>>,
+}
+
+impl RewindBuffer {
+    pub fn new(capacity: usize) -> RewindBuffer {
+        return RewindBuffer {
+            capacity,
+            ram_len: 0,
+            latest: None,
+            // grows as frames come in, so a long rewind does not reserve its whole length up front
+            deltas: VecDeque::new(),
+        };
+    }
+
+    pub fn push(&mut self, state: &Chip8State) {
+        let bytes = state_to_bytes(state);
+        self.ram_len = state.ram.len();
+        if let Some(latest) = self.latest.take() {
+            self.deltas.push_back(encode_delta(&latest, &bytes));
+            if self.deltas.len() > self.capacity {
+                self.deltas.pop_front();
+            }
+        }
+        self.latest = Some(bytes);
+    }
+
+    /// Drops the most recent frame and returns the one recorded before it.
+    pub fn pop(&mut self) -> Option<Chip8State> {
+        let delta = self.deltas.pop_back()?;
+        let latest = self.latest.take().unwrap();
+        let previous = decode_delta(&latest, &delta);
+        let state = state_from_bytes(&previous, self.ram_len);
+        self.latest = Some(previous);
+        return Some(state);
+    }
+
+    /// Number of frames that can still be rewound.
+    pub fn len(&self) -> usize {
+        return self.deltas.len();
+    }
+
+    pub fn clear(&mut self) {
+        self.latest = None;
+        self.deltas.clear();
+    }
+}
+
+pub(super) fn state_to_bytes(state: &Chip8State) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(state.ram.len() + 256 + 39 + state.stack.len() * 2);
+    bytes.extend_from_slice(&state.ram);
+    for y in 0..state.display[0].len() {
+        for x in (0..state.display.len()).step_by(8) {
+            let mut packed = 0u8;
+            for bit in 0..8 {
+                if state.display[x + bit][y] {
+                    packed |= 0x80 >> bit;
+                }
+            }
+            bytes.push(packed);
+        }
+    }
+    bytes.extend_from_slice(&state.pc.to_le_bytes());
+    bytes.extend_from_slice(&state.i.to_le_bytes());
+    bytes.extend_from_slice(&state.registers);
+    bytes.push(state.delay_timer);
+    bytes.push(state.sound_timer);
+    bytes.push(state.random.algorithm as u8);
+    bytes.extend_from_slice(&state.random.seed.to_le_bytes());
+    bytes.extend_from_slice(&state.random.state.to_le_bytes());
+    for address in &state.stack {
+        bytes.extend_from_slice(&address.to_le_bytes());
+    }
+    return bytes;
+}
+
+fn state_from_bytes(bytes: &[u8], ram_len: usize) -> Chip8State {
+    let mut state = Chip8State {
+        ram: bytes[..ram_len].to_vec(),
+        display: [[false; 32]; 64],
+        pc: 0,
+        i: 0,
+        stack: Vec::new(),
+        delay_timer: 0,
+        sound_timer: 0,
+        registers: [0; 16],
+        random: Random::new(RandomAlgorithm::Xorshift, 0),
+    };
+
+    let mut offset = ram_len;
+    for y in 0..state.display[0].len() {
+        for x in (0..state.display.len()).step_by(8) {
+            for bit in 0..8 {
+                state.display[x + bit][y] = bytes[offset] & (0x80 >> bit) > 0;
+            }
+            offset += 1;
+        }
+    }
+    state.pc = u16::from_le_bytes([bytes[offset], bytes[offset + 1]]);
+    state.i = u16::from_le_bytes([bytes[offset + 2], bytes[offset + 3]]);
+    offset += 4;
+    state.registers.copy_from_slice(&bytes[offset..offset + 16]);
+    offset += 16;
+    state.delay_timer = bytes[offset];
+    state.sound_timer = bytes[offset + 1];
+    offset += 2;
+    state.random.algorithm = if bytes[offset] == RandomAlgorithm::CosmacVip as u8 {
+        RandomAlgorithm::CosmacVip
+    } else {
+        RandomAlgorithm::Xorshift
+    };
+    state.random.seed = read_u64(&bytes[offset + 1..]);
+    state.random.state = read_u64(&bytes[offset + 9..]);
+    offset += 17;
+    state.stack = bytes[offset..]
+        .chunks_exact(2)
+        .map(|address| u16::from_le_bytes([address[0], address[1]]))
+        .collect();
+    return state;
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    let mut word = [0u8; 8];
+    word.copy_from_slice(&bytes[..8]);
+    return u64::from_le_bytes(word);
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    let mut word = [0u8; 4];
+    word.copy_from_slice(&bytes[..4]);
+    return u32::from_le_bytes(word);
+}
+
+/// Encodes `older XOR newer` as the length of `older` followed by
+/// `(zero run, literal length, literal bytes)` tokens. Lengths are `u32`, since a state holds all
+/// of memory and can be larger than 64 KB.
+fn encode_delta(older: &[u8], newer: &[u8]) -> Vec<u8> {
+    let len = older.len().max(newer.len());
+    let xor = |i: usize| older.get(i).unwrap_or(&0) ^ newer.get(i).unwrap_or(&0);
+
+    let mut delta = Vec::new();
+    delta.extend_from_slice(&(older.len() as u32).to_le_bytes());
+
+    let mut i = 0;
+    while i < len {
+        let zeros_start = i;
+        while i < len && xor(i) == 0 {
+            i += 1;
+        }
+        if i == len {
+            break;
+        }
+        let literal_start = i;
+        while i < len && xor(i) != 0 {
+            i += 1;
+        }
+        delta.extend_from_slice(&((literal_start - zeros_start) as u32).to_le_bytes());
+        delta.extend_from_slice(&((i - literal_start) as u32).to_le_bytes());
+        delta.extend((literal_start..i).map(xor));
+    }
+    return delta;
+}
+
+/// Rebuilds the older frame from the newer one and the delta between them.
+fn decode_delta(newer: &[u8], delta: &[u8]) -> Vec<u8> {
+    let older_len = read_u32(delta) as usize;
+    let mut older = newer.to_vec();
+    older.resize(older_len.max(newer.len()), 0);
+
+    let mut position = 0;
+    let mut offset = 4;
+    while offset < delta.len() {
+        let zeros = read_u32(&delta[offset..]) as usize;
+        let literals = read_u32(&delta[offset + 4..]) as usize;
+        offset += 8;
+        position += zeros;
+        for byte in &delta[offset..offset + literals] {
+            older[position] ^= byte;
+            position += 1;
+        }
+        offset += literals;
+    }
+
+    older.truncate(older_len);
+    return older;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cpu::{Chip8State, Random, RandomAlgorithm};
+    use crate::cpu::rewind::{decode_delta, encode_delta, RewindBuffer};
+
+    fn state(pc: u16) -> Chip8State {
+        let mut state = Chip8State {
+            ram: vec![0; 4096],
+            display: [[false; 32]; 64],
+            pc,
+            i: 0,
+            stack: Vec::new(),
+            delay_timer: 0,
+            sound_timer: 0,
+            registers: [0; 16],
+            random: Random::new(RandomAlgorithm::CosmacVip, pc as u64),
+        };
+        state.ram[pc as usize] = 0xAB;
+        state.display[(pc % 64) as usize][3] = true;
+        return state;
+    }
+
+    #[test]
+    fn delta_round_trip() {
+        let older = vec![1, 2, 3, 0, 0, 7];
+        let newer = vec![1, 2, 4, 0, 0, 7, 9, 9];
+        let delta = encode_delta(&older, &newer);
+        assert_eq!(older, decode_delta(&newer, &delta));
+    }
+
+    #[test]
+    fn rewinds_a_64k_machine() {
+        let large = |pc: u16| {
+            let mut state = state(pc);
+            state.ram.resize(0x10000, 0);
+            state.ram[0xFFFF] = pc as u8;
+            return state;
+        };
+        let older = large(0x200);
+        let mut newer = large(0x202);
+        // a state longer than a u16 can count, with a run of changed bytes almost as long
+        newer.ram[..0xFFF0].fill(0xFF);
+
+        let mut buffer = RewindBuffer::new(10);
+        buffer.push(&older);
+        buffer.push(&newer);
+        buffer.push(&large(0x204));
+        assert_eq!(Some(newer), buffer.pop());
+        assert_eq!(Some(older), buffer.pop());
+    }
+
+    #[test]
+    fn pop_returns_frames_in_reverse_order() {
+        let mut buffer = RewindBuffer::new(10);
+        let mut first = state(0x200);
+        first.stack.push(0x204);
+        buffer.push(&first);
+        buffer.push(&state(0x202));
+        buffer.push(&state(0x204));
+
+        assert_eq!(Some(state(0x202)), buffer.pop());
+        assert_eq!(Some(first), buffer.pop());
+        assert_eq!(None, buffer.pop());
+    }
+
+    #[test]
+    fn oldest_frames_are_dropped() {
+        let mut buffer = RewindBuffer::new(2);
+        for pc in 0..5 {
+            buffer.push(&state(0x200 + pc * 2));
+        }
+
+        assert_eq!(2, buffer.len());
+        assert_eq!(Some(state(0x206)), buffer.pop());
+        assert_eq!(Some(state(0x204)), buffer.pop());
+        assert_eq!(0, buffer.len());
+    }
+}
@@ -0,0 +1,288 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Range;
+
+use crate::cpu::Chip8;
+
+/// The byte was loaded with the ROM or the font, or written since.
+const INITIALISED: u8 = 1;
+/// The program itself wrote the byte.
+const WRITTEN: u8 = 2;
+/// The byte was executed as part of an instruction.
+const EXECUTED: u8 = 4;
+/// The byte was read as data, by DXYN or FX65.
+const DATA: u8 = 8;
+
+/// Calls nested deeper than the 16 levels most interpreters have room for.
+const MAX_CALL_DEPTH: usize = 16;
+
+/// Something a program did that works here but is most likely a bug.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FindingKind {
+    UninitialisedRead,
+    SpritePastRom,
+    IndexOutOfMemory,
+    ReturnWithoutCall,
+    CallsTooDeep,
+    ExecutesData,
+    SelfModifyingCode,
+}
+
+impl fmt::Display for FindingKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            FindingKind::UninitialisedRead => write!(f, "reads memory never written or loaded"),
+            FindingKind::SpritePastRom => write!(f, "draws sprite data past the end of the ROM"),
+            FindingKind::IndexOutOfMemory => write!(f, "points I outside memory"),
+            FindingKind::ReturnWithoutCall => write!(f, "returns without a call"),
+            FindingKind::CallsTooDeep => write!(f, "nests calls deeper than {}", MAX_CALL_DEPTH),
+            FindingKind::ExecutesData => write!(f, "executes bytes read as data"),
+            FindingKind::SelfModifyingCode => write!(f, "modifies code"),
+        };
+    }
+}
+
+/// One kind of problem at one instruction, however often it happened.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Finding {
+    pub kind: FindingKind,
+    /// Address of the instruction.
+    pub pc: u16,
+    /// The memory address or I value involved the first time, if any.
+    pub address: Option<usize>,
+    pub count: u32,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#05x}: {}", self.pc, self.kind)?;
+        if let Some(address) = self.address {
+            write!(f, ", first at {:#05x}", address)?;
+        }
+        if self.count > 1 {
+            write!(f, " ({} times)", self.count)?;
+        }
+        Ok(())
+    }
+}
+
+/// Follows every byte of memory to report suspicious behaviour, like ASan does for C.
+pub(super) struct Sanitizer {
+    memory: Vec<u8>,
+    rom: Range<usize>,
+    findings: Vec<Finding>,
+}
+
+impl Sanitizer {
+    fn new(memory_size: usize) -> Sanitizer {
+        return Sanitizer { memory: vec![0; memory_size], rom: 0..0, findings: Vec::new() };
+    }
+
+    fn report(&mut self, kind: FindingKind, pc: u16, address: Option<usize>) {
+        match self.findings.iter_mut().find(|finding| finding.kind == kind && finding.pc == pc) {
+            Some(finding) => finding.count += 1,
+            None => self.findings.push(Finding { kind, pc, address, count: 1 }),
+        }
+    }
+
+    fn flags(&self, address: usize) -> u8 {
+        return self.memory.get(address).copied().unwrap_or(0);
+    }
+
+    fn mark(&mut self, address: usize, flag: u8) {
+        if let Some(flags) = self.memory.get_mut(address) {
+            *flags |= flag;
+        }
+    }
+
+    /// Forgets what happened to memory when a ROM is loaded into it.
+    fn loaded(&mut self, rom: Range<usize>, font: Range<usize>) {
+        self.memory.fill(0);
+        for address in font.chain(rom.clone()) {
+            self.mark(address, INITIALISED);
+        }
+        self.rom = rom;
+    }
+
+    pub fn executed(&mut self, pc: u16) {
+        for address in [pc as usize, pc as usize + 1] {
+            let flags = self.flags(address);
+            if flags & INITIALISED == 0 {
+                self.report(FindingKind::UninitialisedRead, pc, Some(address));
+            } else if flags & WRITTEN != 0 {
+                self.report(FindingKind::SelfModifyingCode, pc, Some(address));
+            } else if flags & DATA != 0 {
+                self.report(FindingKind::ExecutesData, pc, Some(address));
+            }
+            self.mark(address, EXECUTED);
+        }
+    }
+
+    /// A read by FX65, made before the machine finds out whether the address is in memory.
+    pub fn data_read(&mut self, pc: u16, address: usize) {
+        if address >= self.memory.len() {
+            self.report(FindingKind::IndexOutOfMemory, pc, Some(address));
+        } else if self.flags(address) & INITIALISED == 0 {
+            self.report(FindingKind::UninitialisedRead, pc, Some(address));
+        }
+        self.mark(address, DATA);
+    }
+
+    /// A sprite row read by DXYN.
+    pub fn sprite_read(&mut self, pc: u16, address: usize) {
+        if self.flags(address) & INITIALISED == 0 {
+            let kind = if address >= self.rom.end { FindingKind::SpritePastRom } else { FindingKind::UninitialisedRead };
+            self.report(kind, pc, Some(address));
+        }
+        self.mark(address, DATA);
+    }
+
+    /// A write by FX33 or FX55.
+    pub fn written(&mut self, pc: u16, address: usize) {
+        if self.flags(address) & EXECUTED != 0 {
+            self.report(FindingKind::SelfModifyingCode, pc, Some(address));
+        }
+        self.mark(address, INITIALISED | WRITTEN);
+    }
+
+    /// A write by the host, which sets memory up instead of changing code.
+    pub fn poked(&mut self, address: usize) {
+        self.mark(address, INITIALISED);
+    }
+
+    /// Checked after every instruction.
+    pub fn index(&mut self, pc: u16, i: u16) {
+        if i as usize >= self.memory.len() {
+            self.report(FindingKind::IndexOutOfMemory, pc, Some(i as usize));
+        }
+    }
+
+    pub fn called(&mut self, pc: u16, depth: usize) {
+        if depth > MAX_CALL_DEPTH {
+            self.report(FindingKind::CallsTooDeep, pc, None);
+        }
+    }
+
+    pub fn returned_without_call(&mut self, pc: u16) {
+        self.report(FindingKind::ReturnWithoutCall, pc, None);
+    }
+}
+
+impl Chip8 {
+    /// Watches the program for reads of memory that was never written, sprites past the end of
+    /// the ROM, I outside memory, returns without calls, runaway calls, data being executed and
+    /// self-modifying code, until `sanitizer_summary` is asked. Costs a byte per byte of RAM.
+    pub fn enable_sanitizer(&mut self) {
+        self.sanitizer = Some(Sanitizer::new(self.ram.len()));
+        self.sanitizer_loaded();
+    }
+
+    /// Everything the sanitizer found so far, in the order it was first seen.
+    pub fn sanitizer_findings(&self) -> &[Finding] {
+        return match &self.sanitizer {
+            Some(sanitizer) => &sanitizer.findings,
+            None => &[],
+        };
+    }
+
+    /// A report to print when the machine stops, none unless the sanitizer is enabled.
+    pub fn sanitizer_summary(&self) -> Option<String> {
+        let findings = &self.sanitizer.as_ref()?.findings;
+        let mut summary = match findings.len() {
+            0 => String::from("Sanitizer: no problems found"),
+            1 => String::from("Sanitizer: 1 problem found"),
+            count => format!("Sanitizer: {} problems found", count),
+        };
+        for finding in findings {
+            summary.push_str(&format!("\n  {}", finding));
+        }
+        return Some(summary);
+    }
+
+    pub(super) fn sanitizer_loaded(&mut self) {
+        let font_address = self.options.font_address as usize;
+        let font = font_address..font_address + self.options.font.len();
+        let program_start = self.options.program_start as usize;
+        let rom = program_start..(program_start + self.rom.len()).min(self.ram.len());
+        if let Some(sanitizer) = self.sanitizer.as_mut() {
+            sanitizer.loaded(rom, font);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::basic::{DummyInput, NullDisplay};
+    use crate::cpu::{Chip8, FindingKind};
+
+    fn run(rom: Vec<u8>, steps: usize) -> Chip8 {
+        let mut chip8 = Chip8::new(Box::new(DummyInput {}), Box::new(NullDisplay {}));
+        chip8.enable_sanitizer();
+        chip8.load_rom_bytes(rom);
+        for _ in 0..steps {
+            let _ = chip8.step();
+        }
+        return chip8;
+    }
+
+    fn kinds(chip8: &Chip8) -> Vec<(FindingKind, u16)> {
+        return chip8.sanitizer_findings().iter().map(|finding| (finding.kind, finding.pc)).collect();
+    }
+
+    #[test]
+    fn clean_programs_have_no_findings() {
+        // 6000: V0 = 0, F029: I = sprite of 0, D015: draw it, 1206: loop
+        let chip8 = run(vec![0x60, 0x00, 0xF0, 0x29, 0xD0, 0x15, 0x12, 0x06], 10);
+        assert!(chip8.sanitizer_findings().is_empty());
+        assert_eq!(Some(String::from("Sanitizer: no problems found")), chip8.sanitizer_summary());
+    }
+
+    #[test]
+    fn memory_misuse_is_found() {
+        // A20A: I = 20A, D003: draw 3 rows, the last one past the ROM, A300: I = 300, F065:
+        // load V0 from it, 120A: jump into the sprite, 6001: V0 = 1
+        let chip8 = run(vec![0xA2, 0x0A, 0xD0, 0x03, 0xA3, 0x00, 0xF0, 0x65, 0x12, 0x0A, 0x60, 0x01], 6);
+        assert_eq!(vec![
+            (FindingKind::SpritePastRom, 0x202),
+            (FindingKind::UninitialisedRead, 0x206),
+            (FindingKind::ExecutesData, 0x20A),
+        ], kinds(&chip8));
+        assert_eq!(Some(0x20C), chip8.sanitizer_findings()[0].address);
+    }
+
+    #[test]
+    fn code_changes_and_stack_errors_are_found() {
+        // A200: I = 200, F055: store V0 over the first instruction, 00EE: return without a call
+        let chip8 = run(vec![0xA2, 0x00, 0xF0, 0x55, 0x00, 0xEE], 3);
+        assert_eq!(vec![
+            (FindingKind::SelfModifyingCode, 0x202),
+            (FindingKind::ReturnWithoutCall, 0x204),
+        ], kinds(&chip8));
+
+        // 2200: call itself forever
+        let chip8 = run(vec![0x22, 0x00], 20);
+        assert_eq!(vec![(FindingKind::CallsTooDeep, 0x200)], kinds(&chip8));
+        assert_eq!(4, chip8.sanitizer_findings()[0].count);
+        assert!(chip8.sanitizer_summary().unwrap().contains("0x200: nests calls deeper than 16 (4 times)"));
+    }
+
+    #[test]
+    fn index_outside_memory_is_found() {
+        // 6FFF: VF = FF, AFFF: I = FFF, FF1E: I += VF, 1206: loop
+        let chip8 = run(vec![0x6F, 0xFF, 0xAF, 0xFF, 0xFF, 0x1E, 0x12, 0x06], 4);
+        assert_eq!(vec![(FindingKind::IndexOutOfMemory, 0x204)], kinds(&chip8));
+        assert_eq!(Some(0x10FE), chip8.sanitizer_findings()[0].address);
+    }
+
+    #[test]
+    fn loads_past_the_end_of_memory_are_found() {
+        // 6001: V0 = 1, AFFF: I = FFF, F055: store V0 in the last byte, F165: load V0 and V1
+        let mut chip8 = run(vec![0x60, 0x01, 0xAF, 0xFF, 0xF0, 0x55, 0xF1, 0x65], 3);
+        assert!(chip8.step().is_err());
+        assert_eq!(vec![(FindingKind::IndexOutOfMemory, 0x206)], kinds(&chip8));
+        assert_eq!(Some(0x1000), chip8.sanitizer_findings()[0].address);
+    }
+}
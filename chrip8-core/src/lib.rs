@@ -0,0 +1,14 @@
+//! The CHIP-8 machine, free of any frontend: `cpu` driven through the `Display` and `Input`
+//! traits, and the plain console frontend in `basic`.
+//!
+//! Without the default `std` feature it only needs `alloc`, so that it can run on a
+//! microcontroller: ROMs are loaded from bytes, and the host calls `Chip8::run_frame` 60
+//! times a second and seeds the RNG with `Chip8::set_seed`.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+#[cfg(any(feature = "std", test))]
+pub mod basic;
+pub mod cpu;
@@ -0,0 +1,10 @@
+#!/bin/sh
+# Builds every feature combination the README promises: the no_std core for a Cortex-M4, the
+# core's tests without std, and everything but the window without SDL.
+set -e
+cd "$(dirname "$0")/.."
+
+rustup target add thumbv7em-none-eabihf
+cargo check -p chrip8-core --no-default-features --target thumbv7em-none-eabihf
+cargo test -p chrip8-core --no-default-features
+cargo test --no-default-features
@@ -0,0 +1,338 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU16, AtomicU32, Ordering};
+
+use clap::Parser;
+use serde_json::json;
+
+use chrip8::basic::NullDisplay;
+use chrip8::cpu::{sha1, Chip8, Chip8State, Input, Instruction, Observer, Protection, ProtectionAction, Quirks, RandomAlgorithm, RomDatabase};
+
+mod timendus;
+
+/// Runs a ROM without a window and writes out the final screen and machine state.
+#[derive(Parser)]
+#[command(name = "chip8-headless")]
+struct Args {
+    /// ROM file to run.
+    rom: String,
+
+    /// Number of 60 Hz frames to run.
+    #[arg(long, default_value_t = 60, conflicts_with = "instructions")]
+    frames: u32,
+
+    /// Number of instructions to run, instead of a number of frames. Frames still end early when
+    /// the program waits for the vertical blank, so this can take more frames than it seems.
+    #[arg(long)]
+    instructions: Option<u32>,
+
+    /// Instructions executed per frame, at least 1.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    ipf: Option<u32>,
+
+    /// Quirks preset (chip8, schip, xochip) or a comma separated list of quirks to enable
+    /// [default: from the ROM database, or default].
+    #[arg(long)]
+    quirks: Option<Quirks>,
+
+    /// programs.json of the chip-8-database, used to set up known ROMs.
+    #[arg(long)]
+    database: Option<String>,
+
+    /// Seed for the CXNN random number generator.
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// CXNN random number generator: xorshift or vip.
+    #[arg(long, default_value = "xorshift")]
+    rng: RandomAlgorithm,
+
+    /// Holds a key, as FRAME:KEY or FRAME:KEY:DURATION (in frames, 1 by default). Can be repeated.
+    #[arg(long = "press", value_parser = parse_key_press)]
+    presses: Vec<KeyPress>,
+
+    /// Writes a byte into RAM after loading the ROM, as ADDR=VALUE in hex. Can be repeated.
+    #[arg(long = "poke", value_parser = parse_poke)]
+    pokes: Vec<(u16, u8)>,
+
+    /// Memory regions programs may not write to: `all` or a comma separated list of
+    /// interpreter, font and program.
+    #[arg(long)]
+    protect: Option<Protection>,
+
+    /// What a write to protected memory does: warn, trap (stops the run right after the write) or error.
+    #[arg(long, default_value = "warn")]
+    on_protected_write: ProtectionAction,
+
+    /// Reports suspicious things the ROM does, such as reading memory it never wrote or running
+    /// into its data, with a summary when the machine stops.
+    #[arg(long)]
+    sanitize: bool,
+
+    /// Writes the final framebuffer here; the extension picks the format: .pbm, .png or .txt.
+    #[arg(long)]
+    screen: Option<PathBuf>,
+
+    /// Writes registers, timers, stack and memory here as JSON.
+    #[arg(long)]
+    dump: Option<PathBuf>,
+
+    /// Treats the ROM as Timendus' test suite: runs its tests with every quirks preset and
+    /// prints the verdicts as JSON.
+    #[arg(long)]
+    test_suite: bool,
+}
+
+#[derive(Clone, Debug)]
+struct KeyPress {
+    frame: u32,
+    key: u8,
+    duration: u32,
+}
+
+fn parse_key_press(value: &str) -> Result<KeyPress, String> {
+    let parts: Vec<&str> = value.split(':').collect();
+    if parts.len() < 2 || parts.len() > 3 {
+        return Err(String::from("expected FRAME:KEY[:DURATION]"));
+    }
+
+    let frame = parts[0].parse::<u32>().map_err(|e| e.to_string())?;
+    let key = u8::from_str_radix(parts[1], 16).map_err(|e| e.to_string())?;
+    if key > 0xF {
+        return Err(format!("key {:X} is not on the hex keypad", key));
+    }
+    let duration = match parts.get(2) {
+        Some(duration) => duration.parse::<u32>().map_err(|e| e.to_string())?,
+        None => 1,
+    };
+    return Ok(KeyPress { frame, key, duration });
+}
+
+fn parse_poke(value: &str) -> Result<(u16, u8), String> {
+    let (address, byte) = value.split_once('=').ok_or("expected ADDR=VALUE")?;
+    let address = u16::from_str_radix(address.trim_start_matches("0x"), 16).map_err(|e| e.to_string())?;
+    let byte = u8::from_str_radix(byte.trim_start_matches("0x"), 16).map_err(|e| e.to_string())?;
+    return Ok((address, byte));
+}
+
+/// Keypad driven by the `--press` script, updated before every frame; clones share the keypad.
+#[derive(Clone)]
+struct ScriptedInput {
+    presses: Vec<KeyPress>,
+    keypad: Arc<AtomicU16>,
+}
+
+impl ScriptedInput {
+    fn set_frame(&self, frame: u32) {
+        let keypad = self.presses.iter()
+            .filter(|press| frame >= press.frame && frame < press.frame.saturating_add(press.duration))
+            .fold(0u16, |keypad, press| keypad | (1 << press.key));
+        self.keypad.store(keypad, Ordering::SeqCst);
+    }
+}
+
+impl Input for ScriptedInput {
+    fn is_key_pressed(&self, key: u8) -> bool {
+        return self.keypad.load(Ordering::SeqCst) & (1 << key) > 0;
+    }
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    if args.test_suite {
+        return run_test_suite(&args.rom);
+    }
+
+    let input = ScriptedInput { presses: args.presses.clone(), keypad: Arc::new(AtomicU16::new(0)) };
+    let mut chip8 = Chip8::new(Box::new(input.clone()), Box::new(NullDisplay {}));
+    chip8.set_random_algorithm(args.rng);
+    chip8.set_seed(args.seed);
+    if let Some(path) = &args.database {
+        match RomDatabase::load(path) {
+            Ok(database) => chip8.set_database(database),
+            Err(e) => {
+                eprintln!("{}", e);
+                return ExitCode::from(2);
+            }
+        }
+    }
+    if let Err(e) = chip8.load_rom_file(args.rom.clone()) {
+        eprintln!("Cannot load {}: {}", args.rom, e);
+        return ExitCode::from(2);
+    }
+    if let Some(quirks) = args.quirks {
+        chip8.set_quirks(quirks);
+    }
+    if let Some(ipf) = args.ipf {
+        if let Err(e) = chip8.set_instructions_per_frame(ipf) {
+            eprintln!("{}", e);
+            return ExitCode::from(2);
+        }
+    }
+    if args.sanitize {
+        chip8.enable_sanitizer();
+    }
+    chip8.set_protection(args.protect.map(|protection| Protection { action: args.on_protected_write, ..protection }));
+    for (address, value) in &args.pokes {
+        chip8.debugger().poke(*address, *value);
+    }
+
+    let mut counts = Counts { frames: 0, instructions: Arc::new(AtomicU32::new(0)) };
+    let result = run(&mut chip8, &input, &args, &mut counts);
+    if let Some(summary) = chip8.sanitizer_summary() {
+        eprintln!("{}", summary);
+    }
+    let state = chip8.save_state();
+
+    if let Err(e) = write_outputs(&args, &state, &counts) {
+        eprintln!("Cannot write output: {}", e);
+        return ExitCode::from(2);
+    }
+
+    return match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Emulation stopped at {:#05x}: {}", state.pc, e);
+            ExitCode::FAILURE
+        }
+    };
+}
+
+fn run_test_suite(rom: &str) -> ExitCode {
+    let bytes = match std::fs::read(rom) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            eprintln!("Cannot load {}: {}", rom, e);
+            return ExitCode::from(2);
+        }
+    };
+    return match timendus::run_suite(&bytes) {
+        Ok(report) => {
+            println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("Test suite stopped: {}", e);
+            ExitCode::FAILURE
+        }
+    };
+}
+
+/// What actually ran, which is less than asked for when the program exits or fails.
+struct Counts {
+    frames: u32,
+    instructions: Arc<AtomicU32>,
+}
+
+/// Counts the instructions that ran without error.
+struct InstructionCounter {
+    instructions: Arc<AtomicU32>,
+}
+
+impl Observer for InstructionCounter {
+    fn after_instruction(&mut self, _pc: u16, _instruction: Instruction) {
+        self.instructions.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Runs `--frames` frames, or frames until exactly `--instructions` instructions ran.
+fn run(chip8: &mut Chip8, input: &ScriptedInput, args: &Args, counts: &mut Counts) -> Result<(), String> {
+    chip8.add_observer(Box::new(InstructionCounter { instructions: counts.instructions.clone() }));
+    loop {
+        let executed = counts.instructions.load(Ordering::SeqCst);
+        let limit = match args.instructions {
+            Some(instructions) if executed < instructions => (instructions - executed).min(chip8.instructions_per_frame()),
+            None if counts.frames < args.frames => chip8.instructions_per_frame(),
+            _ => return Ok(()),
+        };
+        if chip8.has_exited() {
+            return Ok(());
+        }
+
+        input.set_frame(counts.frames);
+        counts.frames += 1;
+        chip8.run_frame_for(limit)?;
+        if let Some(reason) = chip8.take_trap() {
+            return Err(reason);
+        }
+    }
+}
+
+fn write_outputs(args: &Args, state: &Chip8State, counts: &Counts) -> std::io::Result<()> {
+    match &args.screen {
+        Some(path) => write_screen(path, state)?,
+        None if args.dump.is_none() => print!("{}", to_ascii(&state.display)),
+        None => {}
+    }
+
+    if let Some(path) = &args.dump {
+        let dump = json!({
+            "rom": args.rom,
+            "sha1": std::fs::read(&args.rom).map(|rom| sha1(&rom)).ok(),
+            "frames": counts.frames,
+            "instructions": counts.instructions.load(Ordering::SeqCst),
+            "pc": state.pc,
+            "i": state.i,
+            "registers": state.registers.to_vec(),
+            "stack": state.stack,
+            "delay_timer": state.delay_timer,
+            "sound_timer": state.sound_timer,
+            "ram": state.ram.to_vec(),
+        });
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(&mut writer, &dump)?;
+        writeln!(writer)?;
+    }
+
+    Ok(())
+}
+
+fn write_screen(path: &Path, state: &Chip8State) -> std::io::Result<()> {
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    return match extension {
+        "pbm" => std::fs::write(path, to_pbm(&state.display)),
+        "txt" => std::fs::write(path, to_ascii(&state.display)),
+        "png" => write_png(path, &state.display),
+        _ => Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("unknown screen format: {}", path.display()))),
+    };
+}
+
+fn to_ascii(display: &[[bool; 32]; 64]) -> String {
+    let mut ascii = String::new();
+    for y in 0..display[0].len() {
+        for column in display.iter() {
+            ascii.push(if column[y] { '#' } else { '.' });
+        }
+        ascii.push('\n');
+    }
+    return ascii;
+}
+
+/// Plain (P1) portable bitmap, where 1 is a lit pixel.
+fn to_pbm(display: &[[bool; 32]; 64]) -> String {
+    let mut pbm = format!("P1\n{} {}\n", display.len(), display[0].len());
+    for y in 0..display[0].len() {
+        let row: Vec<&str> = display.iter().map(|column| if column[y] { "1" } else { "0" }).collect();
+        pbm.push_str(&row.join(" "));
+        pbm.push('\n');
+    }
+    return pbm;
+}
+
+fn write_png(path: &Path, display: &[[bool; 32]; 64]) -> std::io::Result<()> {
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), display.len() as u32, display[0].len() as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut pixels = Vec::with_capacity(display.len() * display[0].len());
+    for y in 0..display[0].len() {
+        pixels.extend(display.iter().map(|column| if column[y] { 0xFF } else { 0x00 }));
+    }
+
+    let mut writer = encoder.write_header().map_err(std::io::Error::other)?;
+    writer.write_image_data(&pixels).map_err(std::io::Error::other)?;
+    Ok(())
+}
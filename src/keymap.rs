@@ -0,0 +1,252 @@
+use std::fs;
+
+/// Which keyboard keys and controller buttons stand for which digit of the hex keypad. Keys
+/// are named by their label the way SDL names them (`1`, `Q`, `Keypad 7`, `Up`, ...), so that
+/// the map does not depend on a frontend, and a layout follows the letters printed on the
+/// keyboard. Controller buttons are named in `PAD_BUTTONS`.
+///
+/// A keymap file has `<hex digit> = <key>, <key>, ...` lines, each replacing the keys of one
+/// digit; a key bound this way is taken away from any other digit. A file may start from a
+/// built-in layout with `layout = <name>`. Blank lines and lines starting with `#` are
+/// ignored. A `[<ROM file name or SHA-1>]` line starts a section that only applies to that
+/// ROM, on top of everything above the first section:
+///
+/// ```text
+/// layout = azerty
+/// 5 = Z, Up
+///
+/// [Space Invaders [David Winter].ch8]
+/// 4 = Left, Pad Left
+/// 5 = Space, Pad A
+/// 6 = Right, Pad Right
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Keymap {
+    keys: [Vec<String>; 16],
+    roms: Vec<(String, Keymap)>,
+}
+
+/// Keys for the keypad digits 0 to F, laid out like the COSMAC VIP keypad:
+///
+/// ```text
+/// 1 2 3 C
+/// 4 5 6 D
+/// 7 8 9 E
+/// A 0 B F
+/// ```
+const QWERTY: [&str; 16] = ["X", "1", "2", "3", "Q", "W", "E", "A", "S", "D", "Z", "C", "4", "R", "F", "V"];
+const AZERTY: [&str; 16] = ["X", "1", "2", "3", "A", "Z", "E", "Q", "S", "D", "W", "C", "4", "R", "F", "V"];
+const DVORAK: [&str; 16] = ["Q", "1", "2", "3", "'", ",", ".", "A", "O", "E", ";", "J", "4", "P", "U", "K"];
+/// The numbers on the numeric keypad, so that 2, 4, 6 and 8 are where the arrows are.
+const NUMPAD: [&str; 16] = [
+    "Keypad 0", "Keypad 1", "Keypad 2", "Keypad 3", "Keypad 4", "Keypad 5", "Keypad 6", "Keypad 7",
+    "Keypad 8", "Keypad 9", "Keypad /", "Keypad *", "Keypad -", "Keypad +", "Keypad Enter", "Keypad .",
+];
+
+pub const LAYOUTS: [&str; 4] = ["qwerty", "azerty", "dvorak", "numpad"];
+
+/// Controller buttons: the d-pad, the face buttons by their Xbox names, the shoulders, start
+/// and back.
+pub const PAD_BUTTONS: [&str; 12] = [
+    "Pad Up", "Pad Down", "Pad Left", "Pad Right", "Pad A", "Pad B", "Pad X", "Pad Y",
+    "Pad LB", "Pad RB", "Pad Start", "Pad Back",
+];
+
+/// Controller buttons bound with every layout: the d-pad on W, S, A and D of the QWERTY
+/// layout, which most games use as arrows, and A and B on E and Q.
+const DEFAULT_PAD: [(&str, u8); 6] = [("Pad Up", 0x5), ("Pad Down", 0x8), ("Pad Left", 0x7), ("Pad Right", 0x9), ("Pad A", 0x6), ("Pad B", 0x4)];
+
+impl Keymap {
+    /// The usual layout, with the left side of a QWERTY keyboard standing in for the keypad.
+    pub fn qwerty() -> Keymap {
+        return Keymap::from_keys(QWERTY);
+    }
+
+    pub fn layout(name: &str) -> Option<Keymap> {
+        return match name.to_lowercase().as_str() {
+            "qwerty" => Some(Keymap::qwerty()),
+            "azerty" => Some(Keymap::from_keys(AZERTY)),
+            "dvorak" => Some(Keymap::from_keys(DVORAK)),
+            "numpad" => Some(Keymap::from_keys(NUMPAD)),
+            _ => None,
+        };
+    }
+
+    fn from_keys(keys: [&str; 16]) -> Keymap {
+        let mut keymap = Keymap { keys: keys.map(|key| vec![String::from(key)]), roms: Vec::new() };
+        for (button, digit) in DEFAULT_PAD {
+            keymap.bind(digit, button);
+        }
+        return keymap;
+    }
+
+    /// Adds a key to a digit, taking it away from any other digit.
+    fn bind(&mut self, digit: u8, key: &str) {
+        for keys in self.keys.iter_mut() {
+            keys.retain(|bound| bound != key);
+        }
+        self.keys[(digit & 0xF) as usize].push(String::from(key));
+    }
+
+    pub fn load(path: &str) -> Result<Keymap, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("Cannot read keymap {}: {}", path, e))?;
+        return Keymap::parse(&text).map_err(|e| format!("{}: {}", path, e));
+    }
+
+    pub fn parse(text: &str) -> Result<Keymap, String> {
+        let mut keymap = Keymap::qwerty();
+        let mut section: Option<(String, Keymap)> = None;
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if line.starts_with('[') && line.ends_with(']') {
+                keymap.roms.extend(section.take());
+                let base = Keymap { keys: keymap.keys.clone(), roms: Vec::new() };
+                section = Some((line[1..line.len() - 1].trim().to_string(), base));
+                continue;
+            }
+
+            let target = match section.as_mut() {
+                Some((_, rom_keymap)) => rom_keymap,
+                None => &mut keymap,
+            };
+            target.apply(line).map_err(|e| format!("line {}: {}", number + 1, e))?;
+        }
+        keymap.roms.extend(section);
+        return Ok(keymap);
+    }
+
+    fn apply(&mut self, line: &str) -> Result<(), String> {
+        let (name, value) = line.split_once('=').ok_or("expected <hex digit> = <key>, ...")?;
+        let (name, value) = (name.trim(), value.trim());
+
+        if name == "layout" {
+            let layout = Keymap::layout(value).ok_or_else(|| format!("unknown layout {}, expected one of {}", value, LAYOUTS.join(", ")))?;
+            self.keys = layout.keys;
+            return Ok(());
+        }
+
+        let digit = u8::from_str_radix(name, 16)
+            .ok()
+            .filter(|digit| *digit <= 0xF)
+            .ok_or_else(|| format!("{} is not a hex keypad digit", name))?;
+        // split on the commas between names, but keep a lone "," as the comma key
+        let keys: Vec<&str> = value.split(", ")
+            .map(|key| key.trim())
+            .filter(|key| !key.is_empty())
+            .collect();
+        if let Some(button) = keys.iter().find(|key| key.starts_with("Pad ") && !PAD_BUTTONS.contains(key)) {
+            return Err(format!("unknown controller button {}, expected one of {}", button, PAD_BUTTONS.join(", ")));
+        }
+
+        self.keys[digit as usize].clear();
+        for key in keys {
+            self.bind(digit, key);
+        }
+        return Ok(());
+    }
+
+    /// The keymap to use for a ROM: the section named after any of the given identifiers
+    /// (typically the file name and the SHA-1), or the general keymap.
+    pub fn for_rom(&self, identifiers: &[&str]) -> Keymap {
+        return match self.section(identifiers) {
+            Some(keymap) => keymap.clone(),
+            None => Keymap { keys: self.keys.clone(), roms: Vec::new() },
+        };
+    }
+
+    pub fn has_section(&self, identifiers: &[&str]) -> bool {
+        return self.section(identifiers).is_some();
+    }
+
+    fn section(&self, identifiers: &[&str]) -> Option<&Keymap> {
+        return self.roms.iter()
+            .find(|(name, _)| identifiers.iter().any(|identifier| identifier.eq_ignore_ascii_case(name)))
+            .map(|(_, keymap)| keymap);
+    }
+
+    /// Binds the arrow keys, space and enter, and the matching controller buttons, to the
+    /// actions a ROM database entry names, e.g. `up = 5`.
+    pub fn add_actions(&mut self, actions: &[(String, u8)]) {
+        for (action, digit) in actions {
+            let keys = match action.as_str() {
+                "up" => ["Up", "Pad Up"],
+                "down" => ["Down", "Pad Down"],
+                "left" => ["Left", "Pad Left"],
+                "right" => ["Right", "Pad Right"],
+                "a" => ["Space", "Pad A"],
+                "b" => ["Return", "Pad B"],
+                _ => continue,
+            };
+            for key in keys {
+                self.bind(*digit, key);
+            }
+        }
+    }
+
+    /// The keys and buttons bound to each digit, 0 to F.
+    pub fn keys(&self) -> &[Vec<String>; 16] {
+        return &self.keys;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::keymap::Keymap;
+
+    #[test]
+    fn file_overrides_qwerty() {
+        let keymap = Keymap::parse("# arrows\n5 = W, Up\n8 = Down\n\nc = Space\n").unwrap();
+
+        assert_eq!(vec!["W", "Up"], keymap.keys()[0x5]);
+        assert_eq!(vec!["Down"], keymap.keys()[0x8]);
+        assert_eq!(vec!["Space"], keymap.keys()[0xC]);
+        assert_eq!(vec!["X"], keymap.keys()[0x0]);
+        assert_eq!(vec!["E", "Pad A"], keymap.keys()[0x6]);
+    }
+
+    #[test]
+    fn rejects_unknown_digits_and_layouts() {
+        assert!(Keymap::parse("10 = Q").is_err());
+        assert!(Keymap::parse("Q").is_err());
+        assert!(Keymap::parse("layout = colemak").is_err());
+        assert!(Keymap::parse("5 = Pad Z").is_err());
+    }
+
+    #[test]
+    fn layouts() {
+        let keymap = Keymap::parse("layout = dvorak\n7 = A, ,").unwrap();
+        assert_eq!(vec!["A", ","], keymap.keys()[0x7]);
+        assert_eq!(vec!["'", "Pad B"], keymap.keys()[0x4]);
+        assert_eq!(Keymap::layout("AZERTY"), Keymap::parse("layout = azerty").ok());
+    }
+
+    #[test]
+    fn per_rom_sections() {
+        let keymap = Keymap::parse("5 = Up\n\n[Pong.ch8]\n4 = Left\n\n[ABCDEF]\nlayout = numpad\n").unwrap();
+
+        let pong = keymap.for_rom(&["Pong.ch8", "0123"]);
+        assert_eq!(vec!["Left"], pong.keys()[0x4]);
+        assert_eq!(vec!["Up"], pong.keys()[0x5]);
+
+        let numpad = keymap.for_rom(&["other.ch8", "abcdef"]);
+        assert_eq!(vec!["Keypad 5", "Pad Up"], numpad.keys()[0x5]);
+
+        assert_eq!(vec!["Q", "Pad B"], keymap.for_rom(&["other.ch8"]).keys()[0x4]);
+        assert!(keymap.has_section(&["pong.ch8"]));
+        assert!(!keymap.has_section(&["other.ch8"]));
+    }
+
+    #[test]
+    fn database_actions_add_arrow_keys() {
+        let mut keymap = Keymap::qwerty();
+        keymap.add_actions(&[(String::from("left"), 4), (String::from("fire"), 6)]);
+
+        assert_eq!(vec!["Q", "Pad B", "Left", "Pad Left"], keymap.keys()[0x4]);
+        assert_eq!(vec!["A"], keymap.keys()[0x7]);
+        assert_eq!(vec!["E", "Pad A"], keymap.keys()[0x6]);
+    }
+}
@@ -0,0 +1,6 @@
+//! The CHIP-8 emulator: the machine from `chrip8-core`, re-exported as `cpu` and `basic`, and
+//! the keyboard and controller bindings the frontends share in `keymap`. The SDL window, the
+//! terminal and the headless runner are binaries built on top of it.
+
+pub use chrip8_core::{basic, cpu};
+pub mod keymap;
@@ -1,30 +1,329 @@
+use std::fs;
+use std::path::Path;
+use std::process::ExitCode;
+use std::sync::Arc;
 use std::thread;
+
+use clap::Parser;
+use log::{warn, LevelFilter};
+use sdl2::pixels::Color;
 use simple_logger::SimpleLogger;
-use crate::cpu::{Chip8};
-use crate::sdl::{SdlDisplay, SdlInput};
 
-pub mod cpu;
+use chrip8::cpu::{sha1, Chip8, Control, Protection, ProtectionAction, Quirks, RandomAlgorithm, RomDatabase, StopReason};
+use chrip8::keymap::Keymap;
+use crate::sdl::{LoadRom, RomView, Scaling, SdlDisplay, SdlInput, WindowOptions};
+
 mod sdl;
-mod basic;
-
-fn main() {
-    // SimpleLogger::new().init().unwrap();
-    let (sdl_display, display_rx) = SdlDisplay::new();
-    let (sdl_input, input_tx) = SdlInput::new();
-    let keypad = sdl_input.keypad.clone();
-
-    thread::spawn(move || {
-        let mut chip8 = Chip8::new(&sdl_input, &sdl_display);
-        chip8.load_rom_file(String::from("roms/Space Invaders [David Winter].ch8")).expect("File to exists.");
-        chip8.execute().expect("OH NO!");
+
+/// CHIP-8 emulator. Backspace rewinds, F5 pauses, F6 steps one frame while paused, F2 resets,
+/// F11 toggles fullscreen. Drop a ROM file onto the window to run it instead.
+#[derive(Parser)]
+#[command(name = "chrip8")]
+struct Args {
+    /// ROM file to run.
+    rom: String,
+
+    /// Instructions executed per frame, at least 1.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    ipf: Option<u32>,
+
+    /// Size of a CHIP-8 pixel in window pixels when the window opens.
+    #[arg(long, default_value_t = 12, value_parser = clap::value_parser!(u32).range(1..=64))]
+    scale: u32,
+
+    /// How the screen follows the window size: integer (whole multiples) or aspect (as big as
+    /// fits). Both keep pixels square and letterbox the rest.
+    #[arg(long, default_value = "integer")]
+    scaling: Scaling,
+
+    /// Starts in fullscreen; F11 toggles it.
+    #[arg(long)]
+    fullscreen: bool,
+
+    /// Draws a grid between the pixels.
+    #[arg(long)]
+    grid: bool,
+
+    /// Colour of lit pixels, as RRGGBB [default: from the ROM database, or FFFFFF].
+    #[arg(long, value_parser = parse_colour)]
+    foreground: Option<Color>,
+
+    /// Colour of unlit pixels, as RRGGBB [default: from the ROM database, or 000000].
+    #[arg(long, value_parser = parse_colour)]
+    background: Option<Color>,
+
+    /// Share of its brightness a pixel loses every frame once it is turned off, from 0 to 1.
+    /// Values like 0.3 keep flickering sprites visible; 1 turns pixels off at once.
+    #[arg(long, default_value_t = 1.0)]
+    phosphor: f32,
+
+    /// How many seconds Backspace can rewind.
+    #[arg(long, default_value = "10")]
+    rewind_seconds: u32,
+
+    /// Shows the average of the last two frames, which steadies sprites drawn every other frame.
+    #[arg(long)]
+    blend: bool,
+
+    /// Quirks preset (chip8, schip, xochip) or a comma separated list of quirks to enable
+    /// [default: from the ROM database, or default].
+    #[arg(long)]
+    quirks: Option<Quirks>,
+
+    /// Turns on a single quirk on top of the preset. Can be repeated.
+    #[arg(long = "enable-quirk", value_parser = parse_quirk_name)]
+    enabled_quirks: Vec<String>,
+
+    /// Turns off a single quirk of the preset. Can be repeated.
+    #[arg(long = "disable-quirk", value_parser = parse_quirk_name)]
+    disabled_quirks: Vec<String>,
+
+    /// programs.json of the chip-8-database, used to recognise the ROM and set it up.
+    #[arg(long)]
+    database: Option<String>,
+
+    /// Keyboard layout (qwerty, azerty, dvorak, numpad) or a keymap file with
+    /// `<digit> = <key>, ...` lines.
+    #[arg(long, default_value = "qwerty")]
+    keymap: String,
+
+    /// Seed for the CXNN random number generator.
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// CXNN random number generator: xorshift or vip.
+    #[arg(long)]
+    rng: Option<RandomAlgorithm>,
+
+    /// Memory regions programs may not write to: `all` or a comma separated list of
+    /// interpreter, font and program.
+    #[arg(long)]
+    protect: Option<Protection>,
+
+    /// What a write to protected memory does: warn, trap (pauses right after the write) or error.
+    #[arg(long, default_value = "warn")]
+    on_protected_write: ProtectionAction,
+
+    /// Reports suspicious things the ROM does, such as reading memory it never wrote or running
+    /// into its data, with a summary when the machine stops.
+    #[arg(long)]
+    sanitize: bool,
+
+    /// Records the keypad to a movie file.
+    #[arg(long, conflicts_with = "play")]
+    record: Option<String>,
+
+    /// Plays back a movie file instead of reading the keyboard.
+    #[arg(long)]
+    play: Option<String>,
+
+    /// Log level: off, error, warn, info, debug or trace.
+    #[arg(long, default_value = "warn")]
+    log_level: LevelFilter,
+
+    /// Starts paused.
+    #[arg(long)]
+    paused: bool,
+
+    /// Starts paused and logs jumps and register and index writes; implies --paused and
+    /// --log-level debug.
+    #[arg(long)]
+    debug: bool,
+}
+
+fn parse_colour(value: &str) -> Result<Color, String> {
+    let rgb = u32::from_str_radix(value.trim_start_matches('#'), 16).map_err(|e| e.to_string())?;
+    if value.trim_start_matches('#').len() != 6 {
+        return Err(String::from("expected RRGGBB"));
+    }
+    return Ok(Color::RGB((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8));
+}
+
+fn parse_quirk_name(name: &str) -> Result<String, String> {
+    Quirks::default().set(name, true)?;
+    return Ok(name.to_string());
+}
+
+fn main() -> ExitCode {
+    let args = Arc::new(Args::parse());
+    let level = if args.debug { LevelFilter::Debug } else { args.log_level };
+    SimpleLogger::new().with_level(level).init().unwrap();
+
+    let rom = match fs::read(&args.rom) {
+        Ok(rom) => rom,
+        Err(e) => {
+            eprintln!("Cannot load {}: {}", args.rom, e);
+            return ExitCode::from(2);
+        }
+    };
+    let keymap = match Keymap::layout(&args.keymap).map(Ok).unwrap_or_else(|| Keymap::load(&args.keymap)) {
+        Ok(keymap) => keymap,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::from(2);
+        }
+    };
+
+    let database = match args.database.as_deref().map(RomDatabase::load).transpose() {
+        Ok(database) => database,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::from(2);
+        }
+    };
+
+    let (sdl_display, screen) = SdlDisplay::new();
+    let sdl_input = SdlInput::new();
+    let window_input = sdl_input.clone();
+    let view = rom_view(&args, &keymap, database.as_ref(), &args.rom, &rom);
+
+    let mut chip8 = Chip8::new(Box::new(sdl_input), Box::new(sdl_display));
+    if let Err(e) = chip8.enable_rewind(args.rewind_seconds) {
+        eprintln!("{}", e);
+        return ExitCode::from(2);
+    }
+    if let Some(database) = database.clone() {
+        chip8.set_database(database);
+    }
+    chip8.load_rom_bytes(rom);
+    if let Err(e) = configure(&mut chip8, &args).and_then(|_| start_session(&mut chip8, &args)) {
+        eprintln!("{}", e);
+        return ExitCode::from(2);
+    }
+
+    let control = chip8.control();
+    // before the thread starts, so that not even the first frame runs
+    if args.paused || args.debug {
+        control.pause();
+    }
+    let machine = thread::spawn(move || {
+        let reason = chip8.execute();
+        return (reason, chip8.sanitizer_summary());
+    });
+
+    let options = WindowOptions {
+        rom: view,
+        load_rom: load_dropped_rom(args.clone(), keymap, database, control.clone()),
+        scale: args.scale,
+        scaling: args.scaling,
+        fullscreen: args.fullscreen,
+        grid: args.grid,
+        phosphor_decay: args.phosphor,
+        blend_frames: args.blend,
+    };
+
+    // the window also closes when the machine stops
+    let window = SdlDisplay::run(options, window_input, screen, &control);
+    control.stop();
+    let stopped = machine.join();
+
+    if let Err(e) = window {
+        eprintln!("Window error: {}", e);
+        return ExitCode::FAILURE;
+    }
+    if let Ok((_, Some(summary))) = &stopped {
+        eprintln!("{}", summary);
+    }
+    return match stopped {
+        Ok((StopReason::Error(e), _)) => {
+            eprintln!("Emulation stopped: {}", e);
+            ExitCode::FAILURE
+        }
+        Ok(_) => ExitCode::SUCCESS,
+        Err(_) => {
+            eprintln!("The emulation thread panicked");
+            ExitCode::FAILURE
+        }
+    };
+}
+
+/// The window title, keymap and colours of a ROM, from the ROM database, the keymap file's
+/// section for the ROM and the flags.
+fn rom_view(args: &Args, keymap: &Keymap, database: Option<&RomDatabase>, path: &str, rom: &[u8]) -> RomView {
+    let info = database.and_then(|database| database.lookup(rom));
+
+    let file_name = Path::new(path).file_name().and_then(|name| name.to_str()).unwrap_or("");
+    let identifiers = [file_name, &sha1(rom)];
+    let mut rom_keymap = keymap.for_rom(&identifiers);
+    // a section in the keymap file wins over the database
+    if let Some(info) = info.filter(|_| !keymap.has_section(&identifiers)) {
+        rom_keymap.add_actions(&info.keys);
+    }
+    let colour = |index: usize| info.and_then(|info| info.colours.get(index)).and_then(|colour| parse_colour(colour).ok());
+
+    return RomView {
+        title: match info {
+            Some(info) => format!("Chip8 Emulator - {}", info.title),
+            None => String::from("Chip8 Emulator"),
+        },
+        foreground: args.foreground.or_else(|| colour(1)).unwrap_or(Color::RGB(255, 255, 255)),
+        background: args.background.or_else(|| colour(0)).unwrap_or(Color::RGB(0, 0, 0)),
+        keymap: rom_keymap,
+    };
+}
+
+/// Sets a ROM dropped onto the window up the way the command line set up the first one: the
+/// machine loads it, then gets the flags again on top of the database.
+fn load_dropped_rom(args: Arc<Args>, keymap: Keymap, database: Option<RomDatabase>, control: Control) -> LoadRom {
+    return Box::new(move |path: &str| {
+        let rom = fs::read(path).map_err(|e| e.to_string())?;
+        let view = rom_view(&args, &keymap, database.as_ref(), path, &rom);
+        control.load_rom(rom);
+        let args = args.clone();
+        control.configure(move |chip8| {
+            if let Err(e) = configure(chip8, &args) {
+                warn!("{}", e);
+            }
+        });
+        return Ok(view);
     });
+}
 
-    SdlDisplay::run(String::from("Chip8 Emulator"), 800, 600, keypad, display_rx);
+/// Applies the command line on top of what the ROM database set up.
+fn configure(chip8: &mut Chip8, args: &Args) -> Result<(), String> {
+    // flags win over the database
+    let mut quirks = args.quirks.unwrap_or(chip8.quirks());
+    for name in &args.enabled_quirks {
+        quirks.set(name, true)?;
+    }
+    for name in &args.disabled_quirks {
+        quirks.set(name, false)?;
+    }
+    chip8.set_quirks(quirks);
+    if let Some(ipf) = args.ipf {
+        chip8.set_instructions_per_frame(ipf)?;
+    }
+    if let Some(algorithm) = args.rng {
+        chip8.set_random_algorithm(algorithm);
+    }
+    if let Some(seed) = args.seed {
+        chip8.set_seed(seed);
+    }
+    chip8.set_protection(args.protect.map(|protection| Protection { action: args.on_protected_write, ..protection }));
+    Ok(())
+}
+
+/// Sets up what lasts for the whole run rather than for one ROM, so ROMs dropped on the
+/// window neither truncate the movie nor lose what the sanitizer found so far.
+fn start_session(chip8: &mut Chip8, args: &Args) -> Result<(), String> {
+    if args.sanitize {
+        chip8.enable_sanitizer();
+    }
+    if let Some(path) = &args.record {
+        chip8.record_movie(path.clone()).map_err(|e| format!("Cannot record to {}: {}", path, e))?;
+    }
+    if let Some(path) = &args.play {
+        chip8.play_movie(path.clone()).map_err(|e| format!("Cannot play {}: {}", path, e))?;
+    }
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::parse_colour;
+
     #[test]
+    #[allow(clippy::assertions_on_constants)]
     fn add_overflow() {
         let a: u8 = 244u8;
         let b: u8 = 244u8;
@@ -35,4 +334,10 @@ mod tests {
             assert!(false)
         }
     }
+
+    #[test]
+    fn colours() {
+        assert_eq!(Ok((0x12, 0x34, 0x56)), parse_colour("#123456").map(|colour| colour.rgb()));
+        assert!(parse_colour("fff").is_err());
+    }
 }
@@ -1,46 +1,132 @@
-use std::sync::{Arc, mpsc, Mutex};
-use std::sync::mpsc::{Receiver, Sender, SyncSender};
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
 
+use log::warn;
 use sdl2::event::Event;
 use sdl2::keyboard::{Keycode, Scancode};
-use sdl2::pixels::Color;
-use sdl2::rect::Rect;
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::{Point, Rect};
+use sdl2::video::FullscreenType;
 
-use crate::cpu::{Display, Input};
+use chrip8::cpu::{Control, Display, Input};
+use chrip8::keymap::Keymap;
+use crate::sdl::gamepad::Gamepads;
+pub use crate::sdl::layout::Scaling;
+use crate::sdl::layout::{pixel_rect, viewport};
+use crate::sdl::phosphor::{Glow, Phosphor};
+
+mod gamepad;
+mod layout;
+mod phosphor;
+
+type Frame = [[bool; 32]; 64];
+
+/// Fills the window around the screen.
+const LETTERBOX: Color = Color::RGB(0, 0, 0);
 
 pub struct SdlDisplay {
-    display_tx: SyncSender<[[bool; 32]; 64]>,
+    screen: SharedScreen,
+}
+
+/// The last frame the machine finished, waiting for the window to pick it up.
+pub struct Screen {
+    frame: Frame,
+    dirty: bool,
+    /// Set once the machine has stopped and no more frames will come.
+    closed: bool,
+}
+
+pub type SharedScreen = Arc<Mutex<Screen>>;
+
+impl Screen {
+    /// The frame, if it is newer than the one taken last time.
+    fn take(&mut self) -> Option<Frame> {
+        if !self.dirty {
+            return None;
+        }
+        self.dirty = false;
+        return Some(self.frame);
+    }
 }
 
+/// Keyboard and controller state shared between the window and the machine; clones share the same state.
+#[derive(Clone)]
 pub struct SdlInput {
-    pub keypad: Arc<Mutex<u16>>
+    pub keypad: Arc<Mutex<u16>>,
+    pub rewind: Arc<Mutex<bool>>,
+    /// Frames requested with F6 while paused.
+    pub steps: Arc<Mutex<u32>>,
 }
 
-fn to_sdl_rect(display: [[bool; 32]; 64], width: u32, height: u32) -> Vec<Rect> {
-    let mut rects: Vec<Rect> = Vec::new();
-    let ratio_x: u32 = width / display.len() as u32;
-    let ratio_y: u32 = height / display[0].len() as u32;
+/// What the window shows and reads for the ROM that runs.
+pub struct RomView {
+    pub title: String,
+    pub foreground: Color,
+    pub background: Color,
+    pub keymap: Keymap,
+}
 
-    for y in 0..(display[0].len() as u32) {
-        for x in 0..(display.len() as u32) {
-            let pixel = display[(x as usize)][(y as usize)];
-            if pixel {
-                rects.push(Rect::new((x * ratio_x) as i32, (y * ratio_y) as i32, ratio_x, ratio_y));
-            }
+/// Sets up a ROM dropped onto the window and tells the window how to show it.
+pub type LoadRom = Box<dyn FnMut(&str) -> Result<RomView, String>>;
+
+pub struct WindowOptions {
+    pub rom: RomView,
+    /// Called with the path of a file dropped onto the window.
+    pub load_rom: LoadRom,
+    /// Size of a CHIP-8 pixel in window pixels when the window opens.
+    pub scale: u32,
+    pub scaling: Scaling,
+    pub fullscreen: bool,
+    /// Draws lines in the background colour between the pixels.
+    pub grid: bool,
+    /// Share of its brightness a pixel loses every frame once it is off; 1 for no afterglow.
+    pub phosphor_decay: f32,
+    /// Shows the average of the last two frames.
+    pub blend_frames: bool,
+}
+
+/// Writes the screen into an RGB24 texture buffer, one texel per CHIP-8 pixel.
+fn fill_texture(glow: &Glow, buffer: &mut [u8], pitch: usize, background: Color, foreground: Color) {
+    for y in 0..glow[0].len() {
+        for (x, column) in glow.iter().enumerate() {
+            let colour = mix(background, foreground, column[y]);
+            let offset = y * pitch + x * 3;
+            buffer[offset..offset + 3].copy_from_slice(&[colour.r, colour.g, colour.b]);
         }
     }
+}
+
+/// Lines between the CHIP-8 pixels, once they are big enough for a line not to hide them.
+fn grid_lines(viewport: Rect) -> Vec<(Point, Point)> {
+    let mut lines = Vec::new();
+    if viewport.width() < 64 * 4 {
+        return lines;
+    }
+    for x in 1..64 {
+        let left = pixel_rect(viewport, x, 0).left();
+        lines.push((Point::new(left, viewport.top()), Point::new(left, viewport.bottom() - 1)));
+    }
+    for y in 1..32 {
+        let top = pixel_rect(viewport, 0, y).top();
+        lines.push((Point::new(viewport.left(), top), Point::new(viewport.right() - 1, top)));
+    }
+    return lines;
+}
 
-    return rects;
+/// Mixes `to` into `from` by `amount`, from 0 to 1.
+fn mix(from: Color, to: Color, amount: f32) -> Color {
+    let channel = |from: u8, to: u8| (from as f32 + (to as f32 - from as f32) * amount).round() as u8;
+    return Color::RGB(channel(from.r, to.r), channel(from.g, to.g), channel(from.b, to.b));
 }
 
 impl SdlInput {
-    pub fn new() -> (SdlInput, Sender<u8>) {
-        let (input_tx, input_rx): (Sender<u8>, Receiver<u8>) = mpsc::channel();
-        return (SdlInput {
-            keypad: Arc::new(Mutex::new(0x0))
-        }, input_tx);
+    pub fn new() -> SdlInput {
+        return SdlInput {
+            keypad: Arc::new(Mutex::new(0x0)),
+            rewind: Arc::new(Mutex::new(false)),
+            steps: Arc::new(Mutex::new(0)),
+        };
     }
 }
 
@@ -49,93 +135,197 @@ fn press_key(key:u16, keypad: &u16) -> u16 {
 }
 
 impl SdlDisplay {
-    pub fn new() -> (SdlDisplay, Receiver<[[bool; 32]; 64]>) {
-        let (display_tx, display_rx): (SyncSender<[[bool; 32]; 64]>, Receiver<[[bool; 32]; 64]>) = mpsc::sync_channel(1);
+    pub fn new() -> (SdlDisplay, SharedScreen) {
+        let screen = Arc::new(Mutex::new(Screen { frame: [[false; 32]; 64], dirty: false, closed: false }));
 
         return (SdlDisplay {
-            display_tx
-        }, display_rx);
+            screen: screen.clone()
+        }, screen);
     }
 
-    pub fn run(title: String, width: u32, height: u32, keypad: Arc<Mutex<u16>>, rx: Receiver<[[bool; 32]; 64]>) {
-        let sdl_context = sdl2::init().unwrap();
-        let video_subsystem = sdl_context.video().unwrap();
-        let window = video_subsystem.window(title.as_str(), width, height)
+    /// Shows the window until it is closed, or until the machine stops. F5 pauses and F2 resets
+    /// the machine through `control`, and a ROM file dropped onto the window goes to
+    /// `options.load_rom`.
+    pub fn run(mut options: WindowOptions, input: SdlInput, screen: SharedScreen, control: &Control) -> Result<(), String> {
+        let mut rom = options.rom;
+        let mut keys = to_scancodes(&rom.keymap)?;
+        let width = 64 * options.scale;
+        let height = 32 * options.scale;
+
+        let sdl_context = sdl2::init()?;
+        let video_subsystem = sdl_context.video()?;
+        let mut window = video_subsystem.window(rom.title.as_str(), width, height)
             .position_centered()
+            .resizable()
             .build()
-            .unwrap();
-        let mut canvas = window.into_canvas().build().unwrap();
-        let mut event_pump = sdl_context.event_pump().unwrap();
+            .map_err(|e| e.to_string())?;
+        if options.fullscreen {
+            window.set_fullscreen(FullscreenType::Desktop)?;
+        }
+        let mut canvas = window.into_canvas().build().map_err(|e| e.to_string())?;
+        // the screen is one small texture that the renderer scales up; keep its pixels sharp
+        sdl2::hint::set("SDL_RENDER_SCALE_QUALITY", "nearest");
+        let texture_creator = canvas.texture_creator();
+        let mut texture = texture_creator.create_texture_streaming(PixelFormatEnum::RGB24, 64, 32)
+            .map_err(|e| e.to_string())?;
+        let mut event_pump = sdl_context.event_pump()?;
+        let mut gamepads = Gamepads::new(&sdl_context, &rom.keymap)?;
+        let mut phosphor = Phosphor::new(options.phosphor_decay, options.blend_frames);
 
         'running: loop {
-            let result = rx.try_recv();
-            if result.is_ok() {
-                canvas.set_draw_color(Color::RGB(0, 0, 0));
-                canvas.clear();
-
-                canvas.set_draw_color(Color::RGB(255, 255, 255));
-                let rects = to_sdl_rect(result.unwrap(), width, height);
-                for r in rects {
-                    canvas.draw_rect(r).unwrap();
-                    canvas.fill_rect(r).unwrap();
+            {
+                let mut screen = screen.lock().unwrap();
+                if let Some(frame) = screen.take() {
+                    phosphor.show(frame);
+                } else if screen.closed {
+                    break 'running;
                 }
+            }
 
-                canvas.present();
+            // redrawn every frame, so that fading pixels keep fading while the machine is idle
+            let (window_width, window_height) = canvas.output_size()?;
+            let viewport = viewport(window_width, window_height, options.scaling);
+            let glow = phosphor.tick();
+            texture.with_lock(None, |buffer, pitch| fill_texture(glow, buffer, pitch, rom.background, rom.foreground))?;
+            canvas.set_draw_color(LETTERBOX);
+            canvas.clear();
+            canvas.copy(&texture, None, viewport)?;
+            if options.grid {
+                canvas.set_draw_color(rom.background);
+                for (start, end) in grid_lines(viewport) {
+                    canvas.draw_line(start, end)?;
+                }
             }
+            canvas.present();
 
             for event in event_pump.poll_iter() {
+                gamepads.handle_event(&event);
                 match event {
                     Event::Quit { .. } |
                     Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
                         break 'running;
                     }
+                    Event::KeyDown { keycode: Some(Keycode::F5), repeat: false, .. } => {
+                        if control.is_paused() {
+                            control.resume();
+                        } else {
+                            control.pause();
+                        }
+                    }
+                    Event::KeyDown { keycode: Some(Keycode::F2), repeat: false, .. } => {
+                        control.reset();
+                    }
+                    Event::DropFile { filename, .. } => {
+                        match (options.load_rom)(&filename) {
+                            Ok(view) => {
+                                keys = to_scancodes(&view.keymap)?;
+                                gamepads.set_keymap(&view.keymap)?;
+                                canvas.window_mut().set_title(&view.title).map_err(|e| e.to_string())?;
+                                rom = view;
+                            }
+                            Err(e) => warn!("Cannot load {}: {}", filename, e),
+                        }
+                    }
+                    Event::KeyDown { keycode: Some(Keycode::F6), .. } => {
+                        *(input.steps.lock().unwrap()) += 1;
+                    }
+                    Event::KeyDown { keycode: Some(Keycode::F11), repeat: false, .. } => {
+                        let window = canvas.window_mut();
+                        let fullscreen = match window.fullscreen_state() {
+                            FullscreenType::Off => FullscreenType::Desktop,
+                            _ => FullscreenType::Off,
+                        };
+                        window.set_fullscreen(fullscreen)?;
+                    }
                     _ => {}
                 }
             }
 
-            let mut keys : u16 = 0;
+            let mut keypad : u16 = gamepads.keypad();
             for pressed_key in event_pump.keyboard_state().pressed_scancodes() {
-                match pressed_key {
-                    Scancode::Num1 => keys = press_key(0x1, &keys),
-                    Scancode::Num2 => keys = press_key(0x2, &keys),
-                    Scancode::Num3 => keys = press_key(0x3, &keys),
-                    Scancode::Num4 => keys = press_key(0xC, &keys),
-                    Scancode::Q => keys = press_key(0x4, &keys),
-                    Scancode::W => keys = press_key(0x5, &keys),
-                    Scancode::E => keys = press_key(0x6, &keys),
-                    Scancode::R => keys = press_key(0xD, &keys),
-                    Scancode::A => keys = press_key(0x7, &keys),
-                    Scancode::S => keys = press_key(0x8, &keys),
-                    Scancode::D => keys = press_key(0x9, &keys),
-                    Scancode::F => keys = press_key(0xE, &keys),
-                    Scancode::Z => keys = press_key(0xA, &keys),
-                    Scancode::X => keys = press_key(0x0, &keys),
-                    Scancode::C => keys = press_key(0xB, &keys),
-                    Scancode::V => keys = press_key(0xF, &keys),
-
-                    _ => {}
+                for (scancode, key) in &keys {
+                    if pressed_key == *scancode {
+                        keypad = press_key(*key as u16, &keypad);
+                    }
                 }
             }
-            *(keypad.lock().unwrap()) = keys;
+            *(input.keypad.lock().unwrap()) = keypad;
+            *(input.rewind.lock().unwrap()) = event_pump.keyboard_state().is_scancode_pressed(Scancode::Backspace);
 
             thread::sleep(Duration::from_millis(1_000 / 60));
         }
+
+        Ok(())
     }
 }
 
+/// Finds where the labelled keys are on the current keyboard layout.
+fn to_scancodes(keymap: &Keymap) -> Result<Vec<(Scancode, u8)>, String> {
+    let mut scancodes = Vec::new();
+    for (key, names) in keymap.keys().iter().enumerate() {
+        // controller buttons are looked up by the gamepads
+        for name in names.iter().filter(|name| !name.starts_with("Pad ")) {
+            let scancode = Keycode::from_name(name)
+                .and_then(Scancode::from_keycode)
+                .ok_or_else(|| format!("Unknown key in keymap: {}", name))?;
+            scancodes.push((scancode, key as u8));
+        }
+    }
+    return Ok(scancodes);
+}
+
 impl Display for SdlDisplay {
     fn draw(&self, display: [[bool; 32]; 64]) {
-        self.display_tx.send(display).unwrap();
+        let mut screen = self.screen.lock().unwrap();
+        screen.frame = display;
+        screen.dirty = true;
     }
 }
 
-impl Input for SdlInput {
-    fn wait_for_key(&self) -> u8 {
-        return 0x0;
+impl Drop for SdlDisplay {
+    fn drop(&mut self) {
+        self.screen.lock().unwrap().closed = true;
     }
+}
 
+impl Input for SdlInput {
     fn is_key_pressed(&self, key: u8) -> bool {
         let pressed_keys = *self.keypad.lock().unwrap();
         return (pressed_keys & (1 << key)) > 0;
     }
+
+    fn is_rewind_pressed(&self) -> bool {
+        return *self.rewind.lock().unwrap();
+    }
+
+    fn take_step(&self) -> bool {
+        let mut steps = self.steps.lock().unwrap();
+        if *steps == 0 {
+            return false;
+        }
+        *steps -= 1;
+        return true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sdl2::pixels::Color;
+
+    use crate::sdl::fill_texture;
+
+    #[test]
+    fn texture_rows_follow_the_pitch() {
+        let mut glow = [[0.0; 32]; 64];
+        glow[1][0] = 1.0;
+        glow[0][1] = 0.5;
+        // rows padded to 200 bytes, as a renderer may do
+        let mut buffer = vec![0xAA; 200 * 32];
+
+        fill_texture(&glow, &mut buffer, 200, Color::RGB(0, 0, 0), Color::RGB(200, 100, 50));
+
+        assert_eq!([0, 0, 0, 200, 100, 50], buffer[0..6]);
+        assert_eq!(0xAA, buffer[64 * 3]);
+        assert_eq!([100, 50, 25], buffer[200..203]);
+    }
 }
@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+
+use log::{info, warn};
+use sdl2::controller::{Axis, Button, GameController};
+use sdl2::event::Event;
+use sdl2::joystick::{HatState, Joystick};
+use sdl2::{GameControllerSubsystem, JoystickSubsystem, Sdl};
+
+use chrip8::keymap::Keymap;
+
+/// How far a stick has to be pushed to count as the d-pad.
+const STICK_THRESHOLD: i16 = 16_000;
+
+/// Buttons of joysticks SDL has no controller mapping for, in the order they usually come.
+const JOYSTICK_BUTTONS: [Button; 8] = [
+    Button::A, Button::B, Button::X, Button::Y,
+    Button::LeftShoulder, Button::RightShoulder, Button::Back, Button::Start,
+];
+
+/// Every connected controller and joystick, opened and closed as they are plugged in and out.
+pub struct Gamepads {
+    controller_subsystem: GameControllerSubsystem,
+    joystick_subsystem: JoystickSubsystem,
+    controllers: HashMap<u32, GameController>,
+    joysticks: HashMap<u32, Joystick>,
+    buttons: Vec<(Button, u8)>,
+}
+
+impl Gamepads {
+    pub fn new(sdl_context: &Sdl, keymap: &Keymap) -> Result<Gamepads, String> {
+        let buttons = to_buttons(keymap)?;
+        return Ok(Gamepads {
+            controller_subsystem: sdl_context.game_controller()?,
+            joystick_subsystem: sdl_context.joystick()?,
+            controllers: HashMap::new(),
+            joysticks: HashMap::new(),
+            buttons,
+        });
+    }
+
+    /// Binds the buttons anew, for another ROM.
+    pub fn set_keymap(&mut self, keymap: &Keymap) -> Result<(), String> {
+        self.buttons = to_buttons(keymap)?;
+        Ok(())
+    }
+
+    /// Opens and closes devices. SDL also reports the devices present at startup as added.
+    pub fn handle_event(&mut self, event: &Event) {
+        match event {
+            Event::ControllerDeviceAdded { which, .. } => {
+                match self.controller_subsystem.open(*which) {
+                    Ok(controller) => {
+                        info!("Controller connected: {}", controller.name());
+                        self.controllers.insert(controller.instance_id(), controller);
+                    }
+                    Err(e) => warn!("Cannot open controller {}: {}", which, e),
+                }
+            }
+            Event::ControllerDeviceRemoved { which, .. } => {
+                if let Some(controller) = self.controllers.remove(which) {
+                    info!("Controller disconnected: {}", controller.name());
+                }
+            }
+            // devices with a controller mapping are opened as controllers above
+            Event::JoyDeviceAdded { which, .. } if !self.controller_subsystem.is_game_controller(*which) => {
+                match self.joystick_subsystem.open(*which) {
+                    Ok(joystick) => {
+                        info!("Joystick connected: {}", joystick.name());
+                        self.joysticks.insert(joystick.instance_id(), joystick);
+                    }
+                    Err(e) => warn!("Cannot open joystick {}: {}", which, e),
+                }
+            }
+            Event::JoyDeviceRemoved { which, .. } => {
+                if let Some(joystick) = self.joysticks.remove(which) {
+                    info!("Joystick disconnected: {}", joystick.name());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The hex keys held down on any device, as a keypad bitmask.
+    pub fn keypad(&self) -> u16 {
+        let mut pressed = Vec::new();
+        for controller in self.controllers.values() {
+            pressed.extend(controller_buttons(controller));
+        }
+        for joystick in self.joysticks.values() {
+            pressed.extend(joystick_buttons(joystick));
+        }
+
+        return self.buttons.iter()
+            .filter(|(button, _)| pressed.contains(button))
+            .fold(0, |keypad, (_, key)| keypad | (1 << key));
+    }
+}
+
+/// The controller buttons of a keymap and their keypad digits.
+fn to_buttons(keymap: &Keymap) -> Result<Vec<(Button, u8)>, String> {
+    let mut buttons = Vec::new();
+    for (key, names) in keymap.keys().iter().enumerate() {
+        for name in names.iter().filter_map(|name| name.strip_prefix("Pad ")) {
+            let button = to_button(name).ok_or_else(|| format!("Unknown controller button in keymap: {}", name))?;
+            buttons.push((button, key as u8));
+        }
+    }
+    return Ok(buttons);
+}
+
+fn to_button(name: &str) -> Option<Button> {
+    return match name {
+        "Up" => Some(Button::DPadUp),
+        "Down" => Some(Button::DPadDown),
+        "Left" => Some(Button::DPadLeft),
+        "Right" => Some(Button::DPadRight),
+        "A" => Some(Button::A),
+        "B" => Some(Button::B),
+        "X" => Some(Button::X),
+        "Y" => Some(Button::Y),
+        "LB" => Some(Button::LeftShoulder),
+        "RB" => Some(Button::RightShoulder),
+        "Start" => Some(Button::Start),
+        "Back" => Some(Button::Back),
+        _ => None,
+    };
+}
+
+fn controller_buttons(controller: &GameController) -> Vec<Button> {
+    let mut pressed: Vec<Button> = [
+        Button::DPadUp, Button::DPadDown, Button::DPadLeft, Button::DPadRight,
+        Button::A, Button::B, Button::X, Button::Y,
+        Button::LeftShoulder, Button::RightShoulder, Button::Start, Button::Back,
+    ].into_iter().filter(|button| controller.button(*button)).collect();
+
+    pressed.extend(stick_buttons(controller.axis(Axis::LeftX), controller.axis(Axis::LeftY)));
+    return pressed;
+}
+
+fn joystick_buttons(joystick: &Joystick) -> Vec<Button> {
+    let mut pressed: Vec<Button> = JOYSTICK_BUTTONS.iter()
+        .enumerate()
+        .filter(|(index, _)| joystick.button(*index as u32).unwrap_or(false))
+        .map(|(_, button)| *button)
+        .collect();
+
+    if joystick.num_hats() > 0 {
+        let hat = joystick.hat(0).unwrap_or(HatState::Centered) as u8;
+        let directions = [
+            (HatState::Up, Button::DPadUp), (HatState::Down, Button::DPadDown),
+            (HatState::Left, Button::DPadLeft), (HatState::Right, Button::DPadRight),
+        ];
+        for (direction, button) in directions {
+            if hat & direction as u8 > 0 {
+                pressed.push(button);
+            }
+        }
+    }
+    if joystick.num_axes() >= 2 {
+        pressed.extend(stick_buttons(joystick.axis(0).unwrap_or(0), joystick.axis(1).unwrap_or(0)));
+    }
+    return pressed;
+}
+
+/// The d-pad directions a stick is pushed towards.
+fn stick_buttons(x: i16, y: i16) -> Vec<Button> {
+    let mut pressed = Vec::new();
+    if x < -STICK_THRESHOLD {
+        pressed.push(Button::DPadLeft);
+    }
+    if x > STICK_THRESHOLD {
+        pressed.push(Button::DPadRight);
+    }
+    if y < -STICK_THRESHOLD {
+        pressed.push(Button::DPadUp);
+    }
+    if y > STICK_THRESHOLD {
+        pressed.push(Button::DPadDown);
+    }
+    return pressed;
+}
@@ -0,0 +1,86 @@
+use std::str::FromStr;
+
+use sdl2::rect::Rect;
+
+const COLUMNS: u32 = 64;
+const ROWS: u32 = 32;
+
+/// How the 64x32 screen is fitted into the window. Either way pixels stay square and the rest
+/// of the window is letterboxed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Scaling {
+    /// Whole multiples only, so that every CHIP-8 pixel is equally big.
+    Integer,
+    /// As big as fits, possibly with pixels a window pixel wider than their neighbours.
+    Aspect,
+}
+
+impl FromStr for Scaling {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Scaling, String> {
+        return match name {
+            "integer" => Ok(Scaling::Integer),
+            "aspect" => Ok(Scaling::Aspect),
+            _ => Err(format!("Unknown scaling: {}, expected integer or aspect", name)),
+        };
+    }
+}
+
+/// The part of a `width` x `height` window the screen is drawn in, centred.
+pub fn viewport(width: u32, height: u32, scaling: Scaling) -> Rect {
+    let (screen_width, screen_height) = match scaling {
+        Scaling::Integer => {
+            let scale = (width / COLUMNS).min(height / ROWS).max(1);
+            (COLUMNS * scale, ROWS * scale)
+        }
+        Scaling::Aspect => {
+            let scale = (width as f32 / COLUMNS as f32).min(height as f32 / ROWS as f32);
+            (((COLUMNS as f32 * scale) as u32).max(1), ((ROWS as f32 * scale) as u32).max(1))
+        }
+    };
+
+    let x = (width as i32 - screen_width as i32) / 2;
+    let y = (height as i32 - screen_height as i32) / 2;
+    return Rect::new(x, y, screen_width, screen_height);
+}
+
+/// Where a CHIP-8 pixel goes in the viewport. Edges are rounded so that neighbouring pixels
+/// meet without gaps.
+pub fn pixel_rect(viewport: Rect, x: u32, y: u32) -> Rect {
+    let left = viewport.width() * x / COLUMNS;
+    let right = viewport.width() * (x + 1) / COLUMNS;
+    let top = viewport.height() * y / ROWS;
+    let bottom = viewport.height() * (y + 1) / ROWS;
+    return Rect::new(viewport.x() + left as i32, viewport.y() + top as i32, right - left, bottom - top);
+}
+
+#[cfg(test)]
+mod tests {
+    use sdl2::rect::Rect;
+
+    use crate::sdl::layout::{pixel_rect, viewport, Scaling};
+
+    #[test]
+    fn integer_scaling_letterboxes() {
+        assert_eq!(Rect::new(16, 108, 768, 384), viewport(800, 600, Scaling::Integer));
+        assert_eq!(Rect::new(0, 0, 640, 320), viewport(640, 320, Scaling::Integer));
+    }
+
+    #[test]
+    fn aspect_scaling_fills_one_side() {
+        assert_eq!(Rect::new(0, 100, 800, 400), viewport(800, 600, Scaling::Aspect));
+        assert_eq!(Rect::new(50, 0, 700, 350), viewport(800, 350, Scaling::Aspect));
+    }
+
+    #[test]
+    fn pixels_cover_the_viewport() {
+        let viewport = Rect::new(10, 20, 100, 50);
+        assert_eq!(Rect::new(10, 20, 1, 1), pixel_rect(viewport, 0, 0));
+
+        let last = pixel_rect(viewport, 63, 31);
+        assert_eq!(viewport.right(), last.right());
+        assert_eq!(viewport.bottom(), last.bottom());
+        assert_eq!(pixel_rect(viewport, 10, 0).right(), pixel_rect(viewport, 11, 0).left());
+    }
+}
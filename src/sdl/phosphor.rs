@@ -0,0 +1,118 @@
+type Frame = [[bool; 32]; 64];
+pub type Glow = [[f32; 32]; 64];
+
+/// Below this a pixel is as good as dark.
+const DARK: f32 = 1.0 / 255.0;
+
+/// Makes pixels fade out over a few frames, like the phosphor of a CRT, instead of going
+/// dark at once. Sprites that a game erases and redraws every frame then stay visible.
+pub struct Phosphor {
+    /// Share of its brightness a pixel loses every frame once it is turned off: 1 turns it
+    /// off at once, 0.2 lets it glow for a dozen frames.
+    decay: f32,
+    /// Shows the average of the last two frames, which evens out sprites drawn every other frame.
+    blend: bool,
+    current: Frame,
+    /// What `current` was on the last tick, so a frame the machine stops sending blends away.
+    previous: Frame,
+    glow: Glow,
+}
+
+impl Phosphor {
+    pub fn new(decay: f32, blend: bool) -> Phosphor {
+        return Phosphor {
+            decay: decay.clamp(0.0, 1.0),
+            blend,
+            current: [[false; 32]; 64],
+            previous: [[false; 32]; 64],
+            glow: [[0.0; 32]; 64],
+        };
+    }
+
+    /// Takes a new frame from the machine.
+    pub fn show(&mut self, frame: Frame) {
+        self.current = frame;
+    }
+
+    /// Advances one 60 Hz frame and returns how bright every pixel is, from 0 to 1.
+    pub fn tick(&mut self) -> &Glow {
+        for x in 0..self.glow.len() {
+            for y in 0..self.glow[0].len() {
+                let lit: f32 = if self.current[x][y] { 1.0 } else { 0.0 };
+                let target = if self.blend {
+                    (lit + if self.previous[x][y] { 1.0 } else { 0.0 }) / 2.0
+                } else {
+                    lit
+                };
+
+                let faded = self.glow[x][y] * (1.0 - self.decay);
+                let glow = target.max(faded);
+                self.glow[x][y] = if glow < DARK { 0.0 } else { glow };
+            }
+        }
+        self.previous = self.current;
+        return &self.glow;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sdl::phosphor::Phosphor;
+
+    fn frame_with_pixel(lit: bool) -> [[bool; 32]; 64] {
+        let mut frame = [[false; 32]; 64];
+        frame[3][4] = lit;
+        return frame;
+    }
+
+    #[test]
+    fn without_decay_pixels_switch_off_at_once() {
+        let mut phosphor = Phosphor::new(1.0, false);
+        phosphor.show(frame_with_pixel(true));
+        assert_eq!(1.0, phosphor.tick()[3][4]);
+
+        phosphor.show(frame_with_pixel(false));
+        assert_eq!(0.0, phosphor.tick()[3][4]);
+    }
+
+    #[test]
+    fn pixels_fade_out() {
+        let mut phosphor = Phosphor::new(0.5, false);
+        phosphor.show(frame_with_pixel(true));
+        phosphor.tick();
+        phosphor.show(frame_with_pixel(false));
+
+        assert_eq!(0.5, phosphor.tick()[3][4]);
+        assert_eq!(0.25, phosphor.tick()[3][4]);
+        for _ in 0..10 {
+            phosphor.tick();
+        }
+        assert_eq!(0.0, phosphor.tick()[3][4]);
+    }
+
+    #[test]
+    fn blending_averages_two_frames() {
+        let mut phosphor = Phosphor::new(1.0, true);
+        phosphor.show(frame_with_pixel(true));
+        assert_eq!(0.5, phosphor.tick()[3][4]);
+
+        phosphor.show(frame_with_pixel(true));
+        assert_eq!(1.0, phosphor.tick()[3][4]);
+    }
+
+    #[test]
+    fn blending_settles_when_no_new_frame_arrives() {
+        let mut phosphor = Phosphor::new(1.0, true);
+        phosphor.show(frame_with_pixel(true));
+        assert_eq!(0.5, phosphor.tick()[3][4]);
+        for _ in 0..5 {
+            assert_eq!(1.0, phosphor.tick()[3][4]);
+        }
+
+        phosphor.show(frame_with_pixel(false));
+        assert_eq!(0.5, phosphor.tick()[3][4]);
+        for _ in 0..5 {
+            assert_eq!(0.0, phosphor.tick()[3][4]);
+        }
+    }
+}
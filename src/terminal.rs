@@ -0,0 +1,357 @@
+use std::fs;
+use std::io::{self, Stdout, Write};
+use std::path::Path;
+use std::process::ExitCode;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use clap::Parser;
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{
+    self, Event, KeyCode, KeyEvent, KeyEventKind, KeyEventState, KeyModifiers, KeyboardEnhancementFlags,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::style::Print;
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+
+use chrip8::cpu::{sha1, Chip8, Display, Input, Quirks, StopReason};
+use chrip8::keymap::Keymap;
+
+type Frame = [[bool; 32]; 64];
+
+const FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// Runs a ROM in the terminal, two CHIP-8 pixels per character cell. Needs no window, so it
+/// also works over SSH. Esc or Ctrl-C quits.
+#[derive(Parser)]
+#[command(name = "chip8-term")]
+struct Args {
+    /// ROM file to run.
+    rom: String,
+
+    /// Instructions executed per frame, at least 1.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    ipf: Option<u32>,
+
+    /// Quirks preset (chip8, schip, xochip) or a comma separated list of quirks to enable.
+    #[arg(long)]
+    quirks: Option<Quirks>,
+
+    /// Keyboard layout (qwerty, azerty, dvorak, numpad) or a keymap file with `<digit> = <key>, ...`
+    /// lines. The numeric keypad only works in terminals that report key releases.
+    #[arg(long, default_value = "qwerty")]
+    keymap: String,
+
+    /// How long a key counts as held after the terminal last sent it, in milliseconds. Most
+    /// terminals do not report key releases, only the key repeating while it is held.
+    #[arg(long, default_value_t = 150)]
+    hold: u64,
+}
+
+/// The last frame the machine finished, and whether the buzzer went on since the terminal
+/// last looked.
+struct Screen {
+    frame: Frame,
+    dirty: bool,
+    bell: bool,
+    /// Set once the machine has stopped and no more frames will come.
+    closed: bool,
+}
+
+type SharedScreen = Arc<Mutex<Screen>>;
+
+struct TerminalDisplay {
+    screen: SharedScreen,
+}
+
+impl Display for TerminalDisplay {
+    fn draw(&self, display: Frame) {
+        let mut screen = self.screen.lock().unwrap();
+        screen.frame = display;
+        screen.dirty = true;
+    }
+
+    fn sound(&self, on: bool) {
+        if on {
+            self.screen.lock().unwrap().bell = true;
+        }
+    }
+}
+
+impl Drop for TerminalDisplay {
+    fn drop(&mut self) {
+        self.screen.lock().unwrap().closed = true;
+    }
+}
+
+struct TerminalInput {
+    keypad: Arc<Mutex<u16>>,
+}
+
+impl Input for TerminalInput {
+    fn is_key_pressed(&self, key: u8) -> bool {
+        return *self.keypad.lock().unwrap() & (1 << key) != 0;
+    }
+}
+
+/// Raw mode and the alternate screen, given back to the shell when dropped, also on a panic.
+struct RawTerminal {
+    stdout: Stdout,
+    /// The terminal reports key releases, so keys need no hold time, and tells the numeric
+    /// keypad apart from the main keys.
+    releases: bool,
+}
+
+impl RawTerminal {
+    fn enter() -> io::Result<RawTerminal> {
+        terminal::enable_raw_mode()?;
+        let mut stdout = io::stdout();
+        execute!(stdout, EnterAlternateScreen, Hide, Clear(ClearType::All))?;
+
+        let releases = terminal::supports_keyboard_enhancement().unwrap_or(false);
+        if releases {
+            let flags = KeyboardEnhancementFlags::REPORT_EVENT_TYPES | KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES;
+            execute!(stdout, PushKeyboardEnhancementFlags(flags))?;
+        }
+        return Ok(RawTerminal { stdout, releases });
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        if self.releases {
+            let _ = execute!(self.stdout, PopKeyboardEnhancementFlags);
+        }
+        let _ = execute!(self.stdout, Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// The screen as lines of half blocks, the upper half of a cell showing an even row and the
+/// lower half the odd row below it.
+fn render(frame: &Frame) -> Vec<String> {
+    let mut lines = Vec::new();
+    for y in (0..frame[0].len()).step_by(2) {
+        let line = frame.iter()
+            .map(|column| match (column[y], column[y + 1]) {
+                (false, false) => ' ',
+                (true, false) => '▀',
+                (false, true) => '▄',
+                (true, true) => '█',
+            })
+            .collect();
+        lines.push(line);
+    }
+    return lines;
+}
+
+/// The keymap name of a key, the way SDL would name it, so that keymap files work in both
+/// frontends. Only terminals that report key releases mark keys of the numeric keypad.
+fn key_name(key: &KeyEvent) -> Option<String> {
+    if key.state.contains(KeyEventState::KEYPAD) {
+        return match key.code {
+            KeyCode::Char(c) => Some(format!("Keypad {}", c)),
+            KeyCode::Enter => Some(String::from("Keypad Enter")),
+            _ => None,
+        };
+    }
+    return match key.code {
+        KeyCode::Char(' ') => Some(String::from("Space")),
+        KeyCode::Char(c) => Some(c.to_uppercase().to_string()),
+        KeyCode::Up => Some(String::from("Up")),
+        KeyCode::Down => Some(String::from("Down")),
+        KeyCode::Left => Some(String::from("Left")),
+        KeyCode::Right => Some(String::from("Right")),
+        KeyCode::Enter => Some(String::from("Return")),
+        KeyCode::Tab => Some(String::from("Tab")),
+        KeyCode::Backspace => Some(String::from("Backspace")),
+        _ => None,
+    };
+}
+
+fn uses_keypad(keymap: &Keymap) -> bool {
+    return keymap.keys().iter().flatten().any(|name| name.starts_with("Keypad "));
+}
+
+fn is_quit(key: &KeyEvent) -> bool {
+    return key.code == KeyCode::Esc || (key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL));
+}
+
+/// Reads the keyboard and redraws the screen until Esc is pressed or the machine stops.
+fn run(title: &str, keymap: &Keymap, hold: Duration, keypad: &Mutex<u16>, screen: &SharedScreen) -> io::Result<()> {
+    let mut terminal = RawTerminal::enter()?;
+    if !terminal.releases && uses_keypad(keymap) {
+        return Err(io::Error::other("This terminal cannot tell the numeric keypad from the other keys, pick a keymap without Keypad keys"));
+    }
+    // with release events a key is held until it is let go
+    let hold = if terminal.releases { Duration::from_secs(3600) } else { hold };
+    let mut held_until: [Option<Instant>; 16] = [None; 16];
+    let mut redraw = true;
+
+    loop {
+        let frame_start = Instant::now();
+        while event::poll(Duration::ZERO)? {
+            match event::read()? {
+                Event::Key(key) if is_quit(&key) => return Ok(()),
+                Event::Key(key) => {
+                    let name = match key_name(&key) {
+                        Some(name) => name,
+                        None => continue,
+                    };
+                    for (digit, names) in keymap.keys().iter().enumerate() {
+                        if names.iter().any(|bound| bound.eq_ignore_ascii_case(&name)) {
+                            held_until[digit] = match key.kind {
+                                KeyEventKind::Release => None,
+                                _ => Some(frame_start + hold),
+                            };
+                        }
+                    }
+                }
+                Event::Resize(_, _) => redraw = true,
+                _ => {}
+            }
+        }
+
+        let pressed = held_until.iter()
+            .enumerate()
+            .filter(|(_, until)| until.is_some_and(|until| until > frame_start))
+            .fold(0u16, |pressed, (digit, _)| pressed | (1 << digit));
+        *keypad.lock().unwrap() = pressed;
+
+        let (frame, bell, closed) = {
+            let mut screen = screen.lock().unwrap();
+            let frame = if screen.dirty || redraw { Some(screen.frame) } else { None };
+            let bell = screen.bell;
+            screen.dirty = false;
+            screen.bell = false;
+            (frame, bell, screen.closed)
+        };
+        if closed {
+            return Ok(());
+        }
+
+        let stdout = &mut terminal.stdout;
+        if let Some(frame) = frame {
+            if redraw {
+                queue!(stdout, Clear(ClearType::All))?;
+                redraw = false;
+            }
+            for (row, line) in render(&frame).iter().enumerate() {
+                queue!(stdout, MoveTo(0, row as u16), Print(line))?;
+            }
+        }
+        let keys: Vec<String> = (0..16).filter(|digit| pressed & (1 << digit) != 0).map(|digit| format!("{:X}", digit)).collect();
+        let status = format!("{} | keys: {} | Esc quits", title, keys.join(" "));
+        queue!(stdout, MoveTo(0, 16), Print(status), Clear(ClearType::UntilNewLine))?;
+        if bell {
+            queue!(stdout, Print('\x07'))?;
+        }
+        stdout.flush()?;
+
+        let elapsed = frame_start.elapsed();
+        if elapsed < FRAME_DURATION {
+            thread::sleep(FRAME_DURATION - elapsed);
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+
+    let rom = match fs::read(&args.rom) {
+        Ok(rom) => rom,
+        Err(e) => {
+            eprintln!("Cannot load {}: {}", args.rom, e);
+            return ExitCode::from(2);
+        }
+    };
+    let keymap = match Keymap::layout(&args.keymap).map(Ok).unwrap_or_else(|| Keymap::load(&args.keymap)) {
+        Ok(keymap) => keymap,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::from(2);
+        }
+    };
+    let file_name = Path::new(&args.rom).file_name().and_then(|name| name.to_str()).unwrap_or("");
+    let keymap = keymap.for_rom(&[file_name, &sha1(&rom)]);
+
+    let screen = Arc::new(Mutex::new(Screen { frame: [[false; 32]; 64], dirty: false, bell: false, closed: false }));
+    let keypad = Arc::new(Mutex::new(0u16));
+    let display = TerminalDisplay { screen: screen.clone() };
+    let input = TerminalInput { keypad: keypad.clone() };
+
+    let mut chip8 = Chip8::new(Box::new(input), Box::new(display));
+    chip8.load_rom_bytes(rom);
+    if let Some(quirks) = args.quirks {
+        chip8.set_quirks(quirks);
+    }
+    if let Some(ipf) = args.ipf {
+        if let Err(e) = chip8.set_instructions_per_frame(ipf) {
+            eprintln!("{}", e);
+            return ExitCode::from(2);
+        }
+    }
+    let control = chip8.control();
+    let machine = thread::spawn(move || chip8.execute());
+
+    // the terminal also gives up when the machine stops
+    let title = format!("{} at {} ipf", file_name, args.ipf.map_or(String::from("default"), |ipf| ipf.to_string()));
+    let terminal = run(&title, &keymap, Duration::from_millis(args.hold), &keypad, &screen);
+    control.stop();
+    let stopped = machine.join();
+
+    if let Err(e) = terminal {
+        eprintln!("Terminal error: {}", e);
+        return ExitCode::FAILURE;
+    }
+    if let Ok(StopReason::Error(e)) = stopped {
+        eprintln!("Emulation stopped: {}", e);
+        return ExitCode::FAILURE;
+    }
+    return ExitCode::SUCCESS;
+}
+
+#[cfg(test)]
+mod tests {
+    use chrip8::keymap::Keymap;
+    use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyEventState, KeyModifiers};
+
+    use crate::{key_name, render, uses_keypad};
+
+    #[test]
+    fn two_rows_per_line() {
+        let mut frame = [[false; 32]; 64];
+        frame[0][0] = true;
+        frame[1][1] = true;
+        frame[2][0] = true;
+        frame[2][1] = true;
+
+        let lines = render(&frame);
+        assert_eq!(16, lines.len());
+        assert!(lines[0].starts_with("▀▄█ "));
+        assert_eq!(64, lines[15].chars().count());
+    }
+
+    fn key(code: KeyCode, state: KeyEventState) -> KeyEvent {
+        return KeyEvent::new_with_kind_and_state(code, KeyModifiers::NONE, KeyEventKind::Press, state);
+    }
+
+    #[test]
+    fn keys_are_named_like_sdl() {
+        let none = KeyEventState::empty();
+        assert_eq!(Some(String::from("Q")), key_name(&key(KeyCode::Char('q'), none)));
+        assert_eq!(Some(String::from("Space")), key_name(&key(KeyCode::Char(' '), none)));
+        assert_eq!(Some(String::from("Return")), key_name(&key(KeyCode::Enter, none)));
+        assert_eq!(None, key_name(&key(KeyCode::F(1), none)));
+        assert_eq!(Some(String::from("Keypad 7")), key_name(&key(KeyCode::Char('7'), KeyEventState::KEYPAD)));
+        assert_eq!(Some(String::from("Keypad Enter")), key_name(&key(KeyCode::Enter, KeyEventState::KEYPAD)));
+    }
+
+    #[test]
+    fn numpad_layout_needs_the_keypad() {
+        assert!(uses_keypad(&Keymap::layout("numpad").unwrap()));
+        assert!(!uses_keypad(&Keymap::qwerty()));
+    }
+}
@@ -0,0 +1,169 @@
+//! Automated runs of Timendus' CHIP-8 test suite (roms/chip8-test-suite.ch8).
+//!
+//! The suite reads the test to start from 0x1FF and, for the quirks test, the platform
+//! from 0x1FE, so no menu navigation is needed. Results are read back off the screen by
+//! looking for the suite's own verdict sprites.
+
+use serde_json::{json, Map, Value};
+
+use chrip8::basic::{DummyInput, NullDisplay};
+use chrip8::cpu::{Chip8, Quirks};
+
+const TEST_ADDRESS: u16 = 0x1FF;
+const PLATFORM_ADDRESS: u16 = 0x1FE;
+const FRAMES: u32 = 900;
+
+/// Sprites the suite draws for a verdict, as (rows, width, passed).
+const GLYPHS: [(&[u8], usize, bool); 4] = [
+    (&[0xEA, 0xAC, 0xAA, 0xEA], 7, true),  // OK
+    (&[0xCE, 0xAA, 0xAA, 0xAE], 7, false), // NO
+    (&[0xA0, 0xC0, 0x80], 3, true),        // check mark
+    (&[0xA0, 0x40, 0xA0], 3, false),       // cross
+];
+
+/// The quirks test lists its verdicts top to bottom in this order.
+const QUIRKS: [&str; 6] = ["vf_reset", "memory", "display_wait", "clipping", "shifting", "jumping"];
+
+/// Why the keypad test is left out of the report: it shows which keys are held for a person
+/// to compare against the keys they pressed, and draws no verdict sprite to read back.
+const KEYPAD_SKIPPED: &str = "skipped: the keypad test echoes keys back for a person to check and draws no verdict";
+
+/// Presets checked, with the platform to pick in the quirks test.
+const PRESETS: [(&str, u8); 3] = [("chip8", 1), ("schip", 2), ("xochip", 3)];
+
+#[derive(Debug, PartialEq)]
+pub struct Verdict {
+    pub x: usize,
+    pub y: usize,
+    pub passed: bool,
+}
+
+/// Runs the opcode, flags and quirks tests with every preset and returns the report.
+pub fn run_suite(rom: &[u8]) -> Result<Value, String> {
+    let mut report = Map::new();
+    for (preset, platform) in PRESETS {
+        let quirks = Quirks::preset(preset).unwrap();
+        let opcodes = run_test(rom, quirks, 2, 0)?;
+        let flags = run_test(rom, quirks, 3, 0)?;
+        let quirks_test = run_test(rom, quirks, 4, platform)?;
+
+        report.insert(String::from(preset), json!({
+            "opcodes": summary(&opcodes),
+            "flags": summary(&flags),
+            "quirks": quirk_summary(&quirks_test),
+            "keypad": KEYPAD_SKIPPED,
+        }));
+    }
+    return Ok(Value::Object(report));
+}
+
+fn run_test(rom: &[u8], quirks: Quirks, test: u8, platform: u8) -> Result<Vec<Verdict>, String> {
+    let mut chip8 = Chip8::new(Box::new(DummyInput {}), Box::new(NullDisplay {}));
+    chip8.set_quirks(quirks);
+    chip8.set_seed(0);
+    chip8.load_rom_bytes(rom.to_vec());
+    let mut debugger = chip8.debugger();
+    debugger.poke(TEST_ADDRESS, test);
+    debugger.poke(PLATFORM_ADDRESS, platform);
+
+    for _ in 0..FRAMES {
+        chip8.run_frame()?;
+    }
+    return Ok(find_verdicts(&chip8.save_state().display));
+}
+
+fn summary(verdicts: &[Verdict]) -> Value {
+    let failed: Vec<Value> = verdicts.iter()
+        .filter(|verdict| !verdict.passed)
+        .map(|verdict| json!([verdict.x, verdict.y]))
+        .collect();
+    return json!({
+        "passed": verdicts.len() - failed.len(),
+        "failed": failed.len(),
+        "failed_at": failed,
+    });
+}
+
+fn quirk_summary(verdicts: &[Verdict]) -> Value {
+    let mut summary = Map::new();
+    if verdicts.len() != QUIRKS.len() {
+        summary.insert(String::from("error"), json!(format!("expected {} verdicts, found {}", QUIRKS.len(), verdicts.len())));
+        return Value::Object(summary);
+    }
+    for (name, verdict) in QUIRKS.iter().zip(verdicts) {
+        summary.insert(String::from(*name), json!(if verdict.passed { "pass" } else { "fail" }));
+    }
+    return Value::Object(summary);
+}
+
+/// Finds every verdict sprite on the screen, in reading order. A sprite only counts when
+/// the pixels around it are off, so that letters in the labels are not mistaken for one.
+pub fn find_verdicts(display: &[[bool; 32]; 64]) -> Vec<Verdict> {
+    let mut verdicts = Vec::new();
+    for y in 0..display[0].len() {
+        for x in 0..display.len() {
+            for (rows, width, passed) in GLYPHS {
+                if matches_glyph(display, x, y, rows, width) {
+                    verdicts.push(Verdict { x, y, passed });
+                }
+            }
+        }
+    }
+    return verdicts;
+}
+
+fn matches_glyph(display: &[[bool; 32]; 64], x: usize, y: usize, rows: &[u8], width: usize) -> bool {
+    // compare the glyph plus a one pixel border of unlit pixels
+    for dy in -1..=(rows.len() as isize) {
+        for dx in -1..=(width as isize) {
+            let expected = dy >= 0 && (dy as usize) < rows.len() && dx >= 0 && (dx as usize) < width
+                && rows[dy as usize] & (0x80 >> dx) > 0;
+            let px = x as isize + dx;
+            let py = y as isize + dy;
+            let lit = px >= 0 && py >= 0 && (px as usize) < display.len() && (py as usize) < display[0].len()
+                && display[px as usize][py as usize];
+            if lit != expected {
+                return false;
+            }
+        }
+    }
+    return true;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::timendus::{find_verdicts, Verdict};
+
+    fn draw(display: &mut [[bool; 32]; 64], x: usize, y: usize, rows: &[u8]) {
+        for (dy, row) in rows.iter().enumerate() {
+            for dx in 0..8 {
+                if row & (0x80 >> dx) > 0 {
+                    display[x + dx][y + dy] = true;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn finds_verdicts_in_reading_order() {
+        let mut display = [[false; 32]; 64];
+        draw(&mut display, 40, 2, &[0xA0, 0x40, 0xA0]);
+        draw(&mut display, 10, 2, &[0xEA, 0xAC, 0xAA, 0xEA]);
+        draw(&mut display, 10, 10, &[0xA0, 0xC0, 0x80]);
+
+        assert_eq!(vec![
+            Verdict { x: 10, y: 2, passed: true },
+            Verdict { x: 40, y: 2, passed: false },
+            Verdict { x: 10, y: 10, passed: true },
+        ], find_verdicts(&display));
+    }
+
+    #[test]
+    fn ignores_letters_that_start_like_a_check_mark() {
+        let mut display = [[false; 32]; 64];
+        // K: the check mark with one more row underneath
+        draw(&mut display, 5, 5, &[0xA0, 0xC0, 0xA0, 0xA0]);
+
+        assert!(find_verdicts(&display).is_empty());
+    }
+}
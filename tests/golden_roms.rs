@@ -0,0 +1,137 @@
+//! Runs the bundled ROMs through `chip8-headless` and compares the final screen with the
+//! PBM images in `tests/golden`. Run with `UPDATE_GOLDEN=1` to regenerate the images after
+//! an intended change in behaviour.
+
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+fn check_golden(rom: &str, frames: u32, golden: &str) {
+    check_golden_with(rom, frames, &[], golden);
+}
+
+/// Like `check_golden`, with extra `chip8-headless` arguments such as key presses.
+fn check_golden_with(rom: &str, frames: u32, args: &[&str], golden: &str) {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let golden_path = root.join("tests").join("golden").join(format!("{}.pbm", golden));
+    let screen_path = env::temp_dir().join(format!("chip8-golden-{}-{}.pbm", golden, std::process::id()));
+
+    let status = Command::new(env!("CARGO_BIN_EXE_chip8-headless"))
+        .arg(root.join("roms").join(rom))
+        .args(args)
+        .args(["--frames", &frames.to_string(), "--seed", "0", "--screen"])
+        .arg(&screen_path)
+        .status()
+        .expect("chip8-headless to start");
+    assert!(status.success(), "chip8-headless failed on {}", rom);
+
+    let actual = fs::read_to_string(&screen_path).unwrap();
+    fs::remove_file(&screen_path).unwrap();
+
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&golden_path, &actual).unwrap();
+        return;
+    }
+
+    let expected = fs::read_to_string(&golden_path)
+        .unwrap_or_else(|_| panic!("missing {}, run with UPDATE_GOLDEN=1 to create it", golden_path.display()));
+    if expected != actual {
+        panic!("{} differs from {} after {} frames ('+' lit only now, '-' lit only in the golden image):\n{}",
+               rom, golden_path.display(), frames, diff(&expected, &actual));
+    }
+}
+
+fn pixels(pbm: &str) -> Vec<Vec<bool>> {
+    return pbm.lines()
+        .skip(2)
+        .map(|row| row.split_whitespace().map(|pixel| pixel == "1").collect())
+        .collect();
+}
+
+fn diff(expected: &str, actual: &str) -> String {
+    let expected = pixels(expected);
+    let actual = pixels(actual);
+    let mut diff = String::new();
+    for (expected_row, actual_row) in expected.iter().zip(actual.iter()) {
+        for (expected_pixel, actual_pixel) in expected_row.iter().zip(actual_row.iter()) {
+            diff.push(match (expected_pixel, actual_pixel) {
+                (true, true) => '#',
+                (false, false) => '.',
+                (false, true) => '+',
+                (true, false) => '-',
+            });
+        }
+        diff.push('\n');
+    }
+    return diff;
+}
+
+#[test]
+fn ibm_logo() {
+    check_golden("IBM Logo.ch8", 60, "ibm_logo");
+}
+
+#[test]
+fn test_opcode() {
+    check_golden("test_opcode.ch8", 120, "test_opcode");
+}
+
+#[test]
+fn chip8_test_rom() {
+    check_golden("chip8-test-rom.ch8", 300, "chip8_test_rom");
+}
+
+#[test]
+fn chip8_test_suite() {
+    check_golden("chip8-test-suite.ch8", 120, "chip8_test_suite");
+}
+
+#[test]
+fn c8_test() {
+    check_golden("c8_test.c8", 300, "c8_test");
+}
+
+#[test]
+fn sierpinsky() {
+    check_golden("sierpinsky.ch8", 600, "sierpinsky");
+}
+
+#[test]
+fn maze() {
+    check_golden("Maze [David Winter, 199x].ch8", 120, "maze");
+}
+
+#[test]
+fn particle_demo() {
+    check_golden("Particle Demo [zeroZshadow, 2008].ch8", 300, "particle_demo");
+}
+
+#[test]
+fn clock_program() {
+    // the clock waits for the six digits of the time and a key to start: 12:34:59
+    let presses = ["10:1", "20:2", "30:3", "40:4", "50:5", "60:9", "70:0"];
+    let args: Vec<&str> = presses.iter().flat_map(|press| ["--press", press]).collect();
+    check_golden_with("Clock Program [Bill Fisher, 1981].ch8", 120, &args, "clock_program");
+}
+
+#[test]
+fn delay_timer_test() {
+    check_golden("Delay Timer Test [Matthew Mikolay, 2010].ch8", 120, "delay_timer_test");
+}
+
+#[test]
+fn keypad_test() {
+    check_golden("Keypad Test [Hap, 2006].ch8", 120, "keypad_test");
+}
+
+#[test]
+fn space_invaders() {
+    check_golden("Space Invaders [David Winter].ch8", 300, "space_invaders");
+}
+
+#[test]
+fn br8kout() {
+    check_golden("br8kout.ch8", 300, "br8kout");
+}
@@ -0,0 +1,49 @@
+//! Runs Timendus' test suite through `chip8-headless --test-suite` and checks the verdicts
+//! that currently pass, so that regressions show up by name.
+
+
+use std::path::PathBuf;
+use std::process::Command;
+
+use serde_json::Value;
+
+fn report() -> Value {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let output = Command::new(env!("CARGO_BIN_EXE_chip8-headless"))
+        .arg(root.join("roms").join("chip8-test-suite.ch8"))
+        .arg("--test-suite")
+        .output()
+        .expect("chip8-headless to start");
+    assert!(output.status.success(), "chip8-headless failed: {}", String::from_utf8_lossy(&output.stderr));
+    return serde_json::from_slice(&output.stdout).unwrap();
+}
+
+#[test]
+fn presets_pass_their_quirks_test() {
+    let report = report();
+    for preset in ["chip8", "schip", "xochip"] {
+        let quirks = report[preset]["quirks"].as_object().unwrap();
+        assert_eq!(6, quirks.len(), "{}: {:?}", preset, quirks);
+        for (quirk, verdict) in quirks {
+            assert_eq!("pass", verdict, "{} quirk with the {} preset", quirk, preset);
+        }
+    }
+}
+
+#[test]
+fn opcodes_pass() {
+    let report = report();
+    for preset in ["chip8", "schip", "xochip"] {
+        assert_eq!(18, report[preset]["opcodes"]["passed"], "{}", preset);
+        assert_eq!(0, report[preset]["opcodes"]["failed"], "{}", preset);
+    }
+}
+
+#[test]
+fn flags_pass() {
+    let report = report();
+    for preset in ["chip8", "schip", "xochip"] {
+        assert_eq!(0, report[preset]["flags"]["failed"], "{}: {}", preset, report[preset]["flags"]);
+        assert_eq!(40, report[preset]["flags"]["passed"], "{}", preset);
+    }
+}